@@ -3,8 +3,10 @@ use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use crate::{
+    config,
     error::Error,
-    programs::{ProgramBuildResult, SolanaProgram},
+    manifest::sha256_hex,
+    programs::{BuildTarget, ProgramBuildResult, SolanaProgram},
 };
 
 /// Build multiple Solana programs, collecting both successes and failures
@@ -30,34 +32,41 @@ pub fn build_programs(programs: &[SolanaProgram]) -> ProgramBuildResult {
 
 /// Build a single Solana program using cargo build-sbf
 ///
-/// Executes cargo build-sbf on the provided program and returns
+/// Executes cargo build-sbf on the provided program's cargo target and returns
 /// the path to the generated .so file.
 pub fn build_program(program: &SolanaProgram) -> Result<PathBuf, Error> {
-    // Create elf-magic subdirectory for our Solana program builds
-    let sbf_out_dir = std::env::temp_dir()
-        .join("elf-magic-bin")
-        .join(program.package_name.clone());
+    let sbf_out_dir = sbf_out_dir(&std::env::temp_dir(), program);
 
-    // Expected output path for the .so file
-    let program_so_path = sbf_out_dir.join(format!("{}.so", program.target_name));
+    // Expected output path for the .so file (named after the cargo target, not any override)
+    let program_so_path = sbf_out_dir.join(so_file_name(&program.build_target));
 
-    // Remove existing .so file to ensure clean build
-    if program_so_path.exists() {
-        fs::remove_file(&program_so_path).map_err(|e| Error::ProgramBuild {
+    // build-sbf writes into a staging dir; the .so is only replaced when its bytes change,
+    // so unchanged programs keep their mtime and don't trigger downstream recompilation
+    let staging_dir = sbf_out_dir.join("staging");
+    let staged_so_path = staging_dir.join(so_file_name(&program.build_target));
+
+    // Remove the staged .so file to ensure clean build
+    if staged_so_path.exists() {
+        fs::remove_file(&staged_so_path).map_err(|e| Error::ProgramBuild {
             program: program.target_name.clone(),
             error: format!("Failed to remove existing .so file: {}", e),
         })?;
     }
 
     // Execute cargo build-sbf
-    let status = Command::new("cargo")
-        .args([
-            "build-sbf",
-            "--manifest-path",
-            &program.manifest_path.to_string_lossy(),
-            "--sbf-out-dir",
-            &sbf_out_dir.to_string_lossy(),
-        ])
+    let mut command = Command::new("cargo");
+    command
+        .args(build_sbf_args(program, &staging_dir))
+        .envs(&program.build.env);
+    if let Some(target_dir) = &program.build.target_dir {
+        command.env("CARGO_TARGET_DIR", target_dir);
+    }
+    // build-sbf runs cargo on its own before the build, which honors this too
+    if program.build.offline || program.build.frozen {
+        command.env("CARGO_NET_OFFLINE", "true");
+    }
+
+    let status = command
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
         .status()
@@ -76,17 +85,32 @@ pub fn build_program(program: &SolanaProgram) -> Result<PathBuf, Error> {
         });
     }
 
+    // build-sbf only copies the lib target into --sbf-out-dir; pick up examples ourselves
+    if let BuildTarget::Example {
+        target_directory, ..
+    } = &program.build_target
+    {
+        stage_example(program, target_directory, &staged_so_path)?;
+    }
+
     // Verify the .so file was created
-    if !program_so_path.exists() {
+    if !staged_so_path.exists() {
         return Err(Error::ProgramBuild {
             program: program.target_name.clone(),
             error: format!(
                 "Expected .so file not found at: {}",
-                program_so_path.display()
+                staged_so_path.display()
             ),
         });
     }
 
+    fs::read(&staged_so_path)
+        .and_then(|bytes| write_if_changed(&program_so_path, &bytes))
+        .map_err(|e| Error::ProgramBuild {
+            program: program.target_name.clone(),
+            error: format!("Failed to update {}: {}", program_so_path.display(), e),
+        })?;
+
     // Set environment variable for this program
     println!(
         "cargo:rustc-env={}={}",
@@ -97,6 +121,122 @@ pub fn build_program(program: &SolanaProgram) -> Result<PathBuf, Error> {
     Ok(program_so_path)
 }
 
+/// Per-program output directory under `base_dir`
+///
+/// Keyed by a hash of the canonical manifest path as well as the package name, so
+/// same-named packages from different workspaces never share (and overwrite) a `.so`.
+fn sbf_out_dir(base_dir: &Path, program: &SolanaProgram) -> PathBuf {
+    let mut sbf_out_dir = base_dir.join("elf-magic-bin");
+    if let Some(alias) = &program.workspace_alias {
+        sbf_out_dir.push(alias);
+    }
+
+    let manifest_path =
+        fs::canonicalize(&program.manifest_path).unwrap_or_else(|_| program.manifest_path.clone());
+    let manifest_hash = sha256_hex(manifest_path.to_string_lossy().as_bytes());
+    sbf_out_dir.push(format!("{}-{}", program.package_name, &manifest_hash[..16]));
+    sbf_out_dir
+}
+
+/// Copy an example's `.so` from the target directory to `staged_so_path`
+///
+/// The artifact lands under the triple build-sbf compiled for, which depends on `--arch`
+/// and the platform tools (`sbf-solana-solana`, `sbpfv3-solana-solana`, ...). When several
+/// triples hold one, the most recently built wins.
+fn stage_example(
+    program: &SolanaProgram,
+    target_directory: &Path,
+    staged_so_path: &Path,
+) -> Result<(), Error> {
+    let file_name = so_file_name(&program.build_target);
+    let candidates = fs::read_dir(target_directory)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with("-solana-solana")
+        })
+        .map(|entry| {
+            entry
+                .path()
+                .join("release")
+                .join("examples")
+                .join(&file_name)
+        });
+    let newest = candidates
+        .filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+        .max_by_key(|(modified, _)| *modified);
+
+    // A missing artifact is reported by the caller, like a missing lib .so
+    let Some((_, example_so_path)) = newest else {
+        return Ok(());
+    };
+
+    staged_so_path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::copy(&example_so_path, staged_so_path))
+        .map(|_| ())
+        .map_err(|e| Error::ProgramBuild {
+            program: program.target_name.clone(),
+            error: format!("Failed to copy {}: {}", example_so_path.display(), e),
+        })
+}
+
+/// Arguments for `cargo build-sbf`, including the target selection passed through to `cargo build`
+fn build_sbf_args(program: &SolanaProgram, sbf_out_dir: &Path) -> Vec<String> {
+    let mut args = vec![
+        "build-sbf".to_string(),
+        "--manifest-path".to_string(),
+        program.manifest_path.to_string_lossy().to_string(),
+        "--sbf-out-dir".to_string(),
+        sbf_out_dir.to_string_lossy().to_string(),
+    ];
+
+    let build = &program.build;
+    if let Some(tools_version) = &build.tools_version {
+        args.push("--tools-version".to_string());
+        args.push(tools_version.clone());
+    }
+
+    if !build.features.is_empty() {
+        args.push("--features".to_string());
+        args.push(build.features.join(","));
+    }
+
+    args.extend(build.extra_args.iter().cloned());
+
+    // Passed through to `cargo build`, so the build follows the same lockfile policy as discovery
+    args.push("--".to_string());
+    args.extend(program.build_target.cargo_args());
+    args.extend(build.cargo_flags());
+    args
+}
+
+/// Write `contents` to `path` unless it already holds exactly those bytes
+///
+/// Leaving unchanged files alone keeps their mtime, so cargo doesn't rebuild
+/// anything that `include_bytes!`/`include!`s them. Returns whether the file was written.
+pub fn write_if_changed(path: &Path, contents: &[u8]) -> std::io::Result<bool> {
+    if fs::read(path).is_ok_and(|existing| existing == contents) {
+        return Ok(false);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+/// File name of the .so produced for a cargo target
+fn so_file_name(build_target: &BuildTarget) -> String {
+    format!("{}.so", build_target.name())
+}
+
 /// Enable incremental builds for each program
 pub fn enable_incremental_builds(
     manifest_dir: &Path,
@@ -106,6 +246,11 @@ pub fn enable_incremental_builds(
     let src_path = manifest_dir.join("src");
     println!("cargo:rerun-if-changed={}", src_path.display());
 
+    // And config read from outside the crate's own Cargo.toml
+    for config_file in config::inherited_config_files(manifest_dir) {
+        println!("cargo:rerun-if-changed={}", config_file.display());
+    }
+
     // Watch the upstream programs that we're building
     for program in programs {
         let program_root = program.manifest_path.parent().unwrap();
@@ -123,6 +268,12 @@ mod tests {
         SolanaProgram {
             package_name: "test_package".to_string(),
             target_name: "test_target".to_string(),
+            build_target: BuildTarget::Lib("test_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/workspace/Cargo.toml"),
             constant_name: "TEST_TARGET_ELF".to_string(),
         }
@@ -171,12 +322,24 @@ mod tests {
             SolanaProgram {
                 package_name: "pkg1".to_string(),
                 target_name: "target1".to_string(),
+                build_target: BuildTarget::Lib("target1".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
                 manifest_path: PathBuf::from("/workspace/Cargo.toml"),
                 constant_name: "TARGET1_ELF".to_string(),
             },
             SolanaProgram {
                 package_name: "pkg2".to_string(),
                 target_name: "target2".to_string(),
+                build_target: BuildTarget::Lib("target2".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
                 manifest_path: PathBuf::from("/workspace/Cargo.toml"),
                 constant_name: "TARGET2_ELF".to_string(),
             },
@@ -193,6 +356,80 @@ mod tests {
         // The important thing is that it tried to process both programs
     }
 
+    #[test]
+    fn test_so_file_name_uses_cargo_target() {
+        let mut program = sample_program();
+        program.target_name = "renamed".to_string();
+
+        // Overridden target names don't change the artifact cargo produces
+        assert_eq!(so_file_name(&program.build_target), "test_target.so");
+        assert_eq!(
+            so_file_name(&BuildTarget::Example {
+                name: "vault_example".to_string(),
+                target_directory: PathBuf::from("/workspace/target"),
+            }),
+            "vault_example.so"
+        );
+    }
+
+    #[test]
+    fn test_sbf_out_dir_is_keyed_by_manifest() {
+        let base_dir = Path::new("/tmp");
+        let vault = sample_program();
+        let mut other_vault = sample_program();
+        other_vault.manifest_path = PathBuf::from("/other-workspace/Cargo.toml");
+
+        // Same package and target name, different workspaces: different output dirs
+        let out_dir = sbf_out_dir(base_dir, &vault);
+        assert_ne!(out_dir, sbf_out_dir(base_dir, &other_vault));
+        assert_eq!(out_dir, sbf_out_dir(base_dir, &vault));
+        assert!(out_dir.starts_with("/tmp/elf-magic-bin"));
+        assert!(out_dir
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("test_package-"));
+
+        other_vault.workspace_alias = Some("other".to_string());
+        assert!(sbf_out_dir(base_dir, &other_vault).starts_with("/tmp/elf-magic-bin/other"));
+    }
+
+    #[test]
+    fn test_stage_example_finds_artifact_for_any_triple() {
+        let temp_dir = TempDir::new().unwrap();
+        let target_directory = temp_dir.path().join("target");
+        let mut program = sample_program();
+        program.build_target = BuildTarget::Example {
+            name: "vault_example".to_string(),
+            target_directory: target_directory.clone(),
+        };
+        let staged_so_path = temp_dir.path().join("staging").join("vault_example.so");
+
+        let write_artifact = |triple: &str, contents: &[u8]| {
+            let examples_dir = target_directory
+                .join(triple)
+                .join("release")
+                .join("examples");
+            fs::create_dir_all(&examples_dir).unwrap();
+            fs::write(examples_dir.join("vault_example.so"), contents).unwrap();
+        };
+
+        // Nothing built yet: nothing staged, and the caller reports the missing .so
+        stage_example(&program, &target_directory, &staged_so_path).unwrap();
+        assert!(!staged_so_path.exists());
+
+        // `--arch v3` builds into the sbpfv3 triple
+        write_artifact("sbpfv3-solana-solana", b"\x7fELF v3");
+        stage_example(&program, &target_directory, &staged_so_path).unwrap();
+        assert_eq!(fs::read(&staged_so_path).unwrap(), b"\x7fELF v3");
+
+        // A stale artifact from another triple loses to the newest build
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        write_artifact("sbf-solana-solana", b"\x7fELF v1");
+        stage_example(&program, &target_directory, &staged_so_path).unwrap();
+        assert_eq!(fs::read(&staged_so_path).unwrap(), b"\x7fELF v1");
+    }
+
     #[test]
     fn test_sbf_out_dir_creation() {
         let temp_dir = TempDir::new().unwrap();
@@ -205,6 +442,78 @@ mod tests {
         assert_eq!(expected_sbf_dir.file_name().unwrap(), "elf-magic-bin");
     }
 
+    #[test]
+    fn test_build_sbf_args() {
+        let mut program = sample_program();
+        let out_dir = Path::new("/tmp/elf-magic-bin/test_package/staging");
+
+        assert_eq!(
+            build_sbf_args(&program, out_dir),
+            vec![
+                "build-sbf",
+                "--manifest-path",
+                "/workspace/Cargo.toml",
+                "--sbf-out-dir",
+                "/tmp/elf-magic-bin/test_package/staging",
+                "--",
+                "--lib",
+                "--locked",
+            ]
+        );
+
+        program.build.features = vec!["no-entrypoint-log".to_string(), "devnet".to_string()];
+        let args = build_sbf_args(&program, out_dir);
+        assert_eq!(args[5..8], ["--features", "no-entrypoint-log,devnet", "--"]);
+
+        program.build.tools_version = Some("v1.43".to_string());
+        program.build.extra_args = vec!["--arch".to_string(), "v3".to_string()];
+        let args = build_sbf_args(&program, out_dir);
+        assert_eq!(
+            args[5..13],
+            [
+                "--tools-version",
+                "v1.43",
+                "--features",
+                "no-entrypoint-log,devnet",
+                "--arch",
+                "v3",
+                "--",
+                "--lib"
+            ]
+        );
+
+        program.build.locked = false;
+        program.build.offline = true;
+        assert_eq!(
+            build_sbf_args(&program, out_dir).last().map(String::as_str),
+            Some("--offline")
+        );
+        program.build.frozen = true;
+        assert_eq!(
+            build_sbf_args(&program, out_dir).last().map(String::as_str),
+            Some("--frozen")
+        );
+    }
+
+    #[test]
+    fn test_write_if_changed_preserves_unchanged_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested").join("program.so");
+
+        assert!(write_if_changed(&path, b"\x7fELF").unwrap());
+        let first_mtime = fs::metadata(&path).unwrap().modified().unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(!write_if_changed(&path, b"\x7fELF").unwrap());
+        assert_eq!(
+            fs::metadata(&path).unwrap().modified().unwrap(),
+            first_mtime
+        );
+
+        assert!(write_if_changed(&path, b"\x7fELF2").unwrap());
+        assert_eq!(fs::read(&path).unwrap(), b"\x7fELF2");
+    }
+
     #[test]
     fn test_program_so_filename() {
         let programs = vec![
@@ -212,6 +521,12 @@ mod tests {
             SolanaProgram {
                 package_name: "my_package".to_string(),
                 target_name: "my-complex-target-name".to_string(),
+                build_target: BuildTarget::Lib("my-complex-target-name".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
                 manifest_path: PathBuf::from("/workspace/Cargo.toml"),
                 constant_name: "MY_COMPLEX_TARGET_NAME_ELF".to_string(),
             },
@@ -1,11 +1,17 @@
-use std::{fs, path::Path, process::Command};
+use std::path::PathBuf;
 
 use minijinja::{context, Environment};
 
-use crate::{error::Error, programs::ProgramBuildResult};
+use crate::{
+    builder::write_if_changed,
+    config::{closest_match, Alignment, FailureMarker},
+    error::Error,
+    programs::{decode_program_id, ProgramBuildResult},
+};
 
 /// Template for the generated lib.rs file
 const LIB_RS_TEMPLATE: &str = r#"
+// @generated by elf-magic
 // Your generated code here
 // This file is auto-generated by elf-magic
 // DO NOT EDIT - Assume changes will be lost.
@@ -15,41 +21,324 @@ const LIB_RS_TEMPLATE: &str = r#"
 // {{ build_status.icon }} {{ build_status.program_name }} - {{ build_status.message }}
 {% endfor -%}
 // ------------------------------------------------------------
-// Constants
+{% macro program_items(constants, failures) -%}
 {% for constant in constants -%}
-/// ELF binary for the {{ constant.program_name }} Solana program
-pub const {{ constant.constant_name }}: &[u8] = include_bytes!(env!("{{ constant.env_var }}"));
+/// ELF binary for the {{ constant.program_name }} Solana program, aligned to {{ alignment }} bytes
+pub const {{ constant.constant_name }}: &[u8] = {
+    const ALIGNED: &AlignedBytes<[u8]> = &AlignedBytes(*include_bytes!(env!("{{ constant.env_var }}")));
+    &ALIGNED.0
+};
 
 {% endfor -%}
+{% for failure in failures -%}
+{% if failure_marker == "deprecated" -%}
+/// Placeholder for the {{ failure.program_name }} Solana program, which failed to build
+#[deprecated(note = {{ failure.error_literal }})]
+pub const {{ failure.constant_name }}: &[u8] = &[];
 
+{% elif failure_marker == "compile-error" -%}
+compile_error!({{ failure.error_literal }});
+
+{% endif -%}
+{% endfor -%}
+{% for constant in constants -%}
+/// ELF binary for the {{ constant.program_name }} Solana program, honoring `{{ constant.override_env_var }}`
+pub fn {{ constant.accessor_name }}() -> Elf {
+    static ELF: std::sync::OnceLock<Elf> = std::sync::OnceLock::new();
+    *ELF.get_or_init(|| {
+        let (bytes, overridden) = load_elf("{{ constant.override_env_var }}", {{ constant.constant_name }});
+        Elf {
+            name: "{{ constant.program_name }}",
+            bytes,
+            overridden,
+        }
+    })
+}
+
+{% endfor -%}
 /// Get all available Solana program ELF binaries
-/// Returns a vector of (program_name, elf_bytes) tuples
-pub fn elves() -> Vec<(&'static str, &'static [u8])> {
-    vec![
+/// Each entry reports whether its bytes were overridden at runtime
+pub fn elves() -> Vec<Elf> {
+    vec![{% for constant in constants %}{{ constant.accessor_name }}(){% if not loop.last %}, {% endif %}{% endfor %}]
+}
+{%- endmacro %}
+/// A Solana program ELF binary, either embedded at build time or loaded from an override
+#[derive(Debug, Clone, Copy)]
+pub struct Elf {
+    /// Program (target) name
+    pub name: &'static str,
+    /// ELF bytes
+    pub bytes: &'static [u8],
+    /// Whether the bytes were loaded from the program's override env var
+    pub overridden: bool,
+}
+
+/// Embedded ELF bytes, aligned so loaders can parse them without copying
+#[allow(dead_code)]
+#[repr(C, align({{ alignment }}))]
+struct AlignedBytes<B: ?Sized>(B);
+
+/// Load ELF bytes from the override env var if set, otherwise use the embedded bytes
+///
+/// Overrides only apply with debug assertions on, so a stray env var can't swap the
+/// binary a release build deploys.
+#[allow(dead_code)]
+fn load_elf(override_env_var: &str, embedded: &'static [u8]) -> (&'static [u8], bool) {
+    #[cfg(debug_assertions)]
+    if let Some(path) = std::env::var_os(override_env_var) {
+        let bytes = std::fs::read(&path).unwrap_or_else(|e| {
+            panic!("{} is set but {:?} could not be read: {}", override_env_var, path, e)
+        });
+        return (Box::leak(bytes.into_boxed_slice()), true);
+    }
+
+    let _ = override_env_var;
+    (embedded, false)
+}
+
+/// Outcome of building a Solana program
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Status {
+    /// Built and embedded
+    Success,
+    /// `cargo build-sbf` failed with this error
+    Failed(&'static str),
+}
+
+/// Build outcome of every included program, by program name
+pub const BUILD_STATUS: &[(&str, Status)] = &[
+{%- for build_status in build_statuses %}
+    ("{{ build_status.program_name }}", {{ build_status.status }}),
+{%- endfor %}
+];
+
+/// Programs that failed to build, with their build errors
+pub fn failed_programs() -> Vec<(&'static str, &'static str)> {
+    BUILD_STATUS
+        .iter()
+        .filter_map(|(name, status)| match status {
+            Status::Failed(error) => Some((*name, *error)),
+            Status::Success => None,
+        })
+        .collect()
+}
+
+{% if modules -%}
+{% for module in modules -%}
+/// Solana programs from the `{{ module.alias }}` workspace
+pub mod {{ module.alias }} {
+    #[allow(unused_imports)]
+    use super::{load_elf, AlignedBytes, Elf};
+
+    {{ program_items(module.constants, module.failures) | indent(4) }}
+}
+
+{% endfor -%}
+/// Get all available Solana program ELF binaries across every workspace module
+pub fn elves() -> Vec<Elf> {
+    let mut elves = Vec::new();
+{%- for module in modules %}
+    elves.extend({{ module.alias }}::elves());
+{%- endfor %}
+    elves
+}
+{% else -%}
+{{ program_items(constants, failures) }}
+{% endif %}
+/// Test-harness helpers for the embedded programs
+///
+/// Declare and enable this crate's `litesvm`, `mollusk` or `solana-program-test` feature to
+/// get the loader for that framework. Program IDs come from each program's `declare_id!` or
+/// the `program_ids` table in `[package.metadata.elf-magic]`.
+pub mod harness {
+    /// An embedded Solana program
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+    pub enum Program {
 {%- for constant in constants %}
-        ("{{ constant.program_name }}", {{ constant.constant_name }}),
+        /// The {{ constant.program_name }} Solana program
+        {{ constant.variant }},
 {%- endfor %}
-    ]
+    }
+
+    impl Program {
+        /// Every embedded program
+        pub const ALL: &'static [Program] = &[
+{%- for constant in constants %}
+            Program::{{ constant.variant }},
+{%- endfor %}
+        ];
+
+        /// Program name
+        pub fn name(self) -> &'static str {
+            match self {
+{%- for constant in constants %}
+                Program::{{ constant.variant }} => "{{ constant.program_name }}",
+{%- endfor %}
+            }
+        }
+
+        /// ELF binary, honoring runtime overrides
+        pub fn elf(self) -> super::Elf {
+            match self {
+{%- for constant in constants %}
+                Program::{{ constant.variant }} => super::{{ constant.accessor_path }}(),
+{%- endfor %}
+            }
+        }
+
+        /// Program ID, if one was declared or configured
+        pub fn id(self) -> Option<[u8; 32]> {
+            match self {
+{%- for constant in constants %}
+                Program::{{ constant.variant }} => {{ constant.program_id }},
+{%- endfor %}
+            }
+        }
+
+        /// Program ID, panicking with a hint when none is known
+        #[allow(dead_code)]
+        fn expect_id(self) -> [u8; 32] {
+            self.id().unwrap_or_else(|| {
+                panic!(
+                    "No program ID known for {}; add a declare_id! to the program or a `program_ids` entry to [package.metadata.elf-magic]",
+                    self.name()
+                )
+            })
+        }
+    }
+
+{%- if "litesvm" in harness_features %}
+
+    /// Load every program with a known ID into a LiteSVM instance
+    ///
+    /// Programs without one are skipped; [`Program::id`] says which. Panics, naming the
+    /// program, when LiteSVM rejects an ELF.
+    #[cfg(feature = "litesvm")]
+    pub fn add_all_programs(svm: &mut litesvm::LiteSVM) {
+        for program in Program::ALL {
+            if let Some(id) = program.id() {
+                svm.add_program(id.into(), program.elf().bytes)
+                    .unwrap_or_else(|e| {
+                        panic!("LiteSVM rejected the {} program: {:?}", program.name(), e)
+                    });
+            }
+        }
+    }
+
+{%- endif %}
+{%- if "mollusk" in harness_features %}
+
+    /// A Mollusk instance with `program` loaded under its program ID
+    #[cfg(feature = "mollusk")]
+    pub fn mollusk_for(program: Program) -> mollusk_svm::Mollusk {
+        let mut mollusk = mollusk_svm::Mollusk::default();
+        mollusk.add_program_with_elf_and_loader(
+            &program.expect_id().into(),
+            program.elf().bytes,
+            &mollusk_svm::program::loader_keys::LOADER_V3,
+        );
+        mollusk
+    }
+
+{%- endif %}
+{%- if "solana-program-test" in harness_features %}
+
+    /// Add every program with a known ID to a solana-program-test `ProgramTest`
+    ///
+    /// Programs without one are skipped; [`Program::id`] says which.
+    #[cfg(feature = "solana-program-test")]
+    pub fn add_all_programs_to_program_test(program_test: &mut solana_program_test::ProgramTest) {
+        for program in Program::ALL {
+            if program.id().is_some() {
+                add_program_to_program_test(program_test, *program);
+            }
+        }
+    }
+
+    /// Add `program` to a solana-program-test `ProgramTest` as an executable account
+    #[cfg(feature = "solana-program-test")]
+    pub fn add_program_to_program_test(
+        program_test: &mut solana_program_test::ProgramTest,
+        program: Program,
+    ) {
+        let bytes = program.elf().bytes;
+        program_test.add_account(
+            program.expect_id().into(),
+            solana_sdk::account::Account {
+                lamports: solana_sdk::rent::Rent::default()
+                    .minimum_balance(bytes.len())
+                    .max(1),
+                data: bytes.to_vec(),
+                owner: solana_sdk::bpf_loader::id(),
+                executable: true,
+                rent_epoch: 0,
+            },
+        );
+    }
+{%- endif %}
 }
 "#;
 
+/// Cargo features of the ELF crate that turn on a `harness` loader
+pub const HARNESS_FEATURES: &[&str] = &["litesvm", "mollusk", "solana-program-test"];
+
+/// Declared features that look like a misspelled harness feature, with the one they resemble
+pub fn misspelled_harness_features(features: &[String]) -> Vec<(&str, &'static str)> {
+    features
+        .iter()
+        .filter(|feature| !HARNESS_FEATURES.contains(&feature.as_str()))
+        .filter_map(|feature| {
+            let known = closest_match(feature, HARNESS_FEATURES.iter().copied())?;
+            Some((feature.as_str(), known))
+        })
+        .collect()
+}
+
 /// Generate code for Solana programs from build results
-pub fn generate(build_result: &ProgramBuildResult) -> Result<String, Error> {
+///
+/// With no `workspace_modules` every constant lands at the top level (flat layout);
+/// otherwise each program is emitted into the `pub mod` named by its workspace alias.
+/// `features` are the ELF crate's declared cargo features: only the harness loaders it
+/// declares a feature for are emitted, so their `cfg`s are always expected ones.
+pub fn generate(
+    build_result: &ProgramBuildResult,
+    workspace_modules: &[String],
+    failure_marker: FailureMarker,
+    alignment: Alignment,
+    features: &[String],
+) -> Result<String, Error> {
     // Collect all programs with render data in one pass
-    let mut program_specs: Vec<(String, Option<serde_json::Value>, serde_json::Value)> = Vec::new();
+    let mut program_specs: Vec<(
+        String,
+        Result<serde_json::Value, serde_json::Value>,
+        serde_json::Value,
+    )> = Vec::new();
 
     // Process successful programs
     for (program, _path) in &build_result.successful {
-        let constant = Some(serde_json::json!({
+        let constant = Ok(serde_json::json!({
             "constant_name": program.constant_name,
             "env_var": program.env_var_name(),
-            "program_name": program.target_name.clone()
+            "override_env_var": program.override_env_var_name(),
+            "accessor_name": program.accessor_name(),
+            "program_name": program.qualified_name(),
+            "module": program.workspace_alias,
+            "variant": program.variant_name(),
+            "accessor_path": match &program.workspace_alias {
+                Some(alias) => format!("{}::{}", alias, program.accessor_name()),
+                None => program.accessor_name(),
+            },
+            "program_id": match program.program_id.as_deref().and_then(decode_program_id) {
+                Some(bytes) => format!("Some({:?})", bytes),
+                None => "None".to_string(),
+            },
         }));
 
         let build_status = serde_json::json!({
             "icon": "✓",
-            "program_name": program.target_name,
-            "message": "SUCCESS"
+            "program_name": program.qualified_name(),
+            "message": "SUCCESS",
+            "status": "Status::Success",
         });
 
         program_specs.push((program.target_name.clone(), constant, build_status));
@@ -57,13 +346,22 @@ pub fn generate(build_result: &ProgramBuildResult) -> Result<String, Error> {
 
     // Process failed programs
     for (program, error) in &build_result.failed {
+        let failure = Err(serde_json::json!({
+            "constant_name": program.constant_name,
+            "program_name": program.qualified_name(),
+            "module": program.workspace_alias,
+            "error_literal": format!("{:?}", error.to_string()),
+        }));
+
         let build_status = serde_json::json!({
             "icon": "✗",
-            "program_name": program.target_name,
-            "message": format!("FAILED: {}", error)
+            "program_name": program.qualified_name(),
+            // Keep multi-line errors inside their `//` comment
+            "message": format!("FAILED: {}", error).replace('\n', " "),
+            "status": format!("Status::Failed({:?})", error.to_string()),
         });
 
-        program_specs.push((program.target_name.clone(), None, build_status));
+        program_specs.push((program.target_name.clone(), failure, build_status));
     }
 
     // Sort once by target_name
@@ -84,7 +382,38 @@ pub fn generate(build_result: &ProgramBuildResult) -> Result<String, Error> {
 
     let constants: Vec<_> = program_specs
         .iter()
-        .filter_map(|(_, constant, _)| constant.clone())
+        .filter_map(|(_, constant, _)| constant.clone().ok())
+        .collect();
+
+    let failures: Vec<_> = program_specs
+        .iter()
+        .filter_map(|(_, constant, _)| constant.clone().err())
+        .collect();
+
+    let in_module = |item: &&serde_json::Value, alias: &str| item["module"].as_str() == Some(alias);
+
+    let modules: Vec<_> = workspace_modules
+        .iter()
+        .map(|alias| {
+            let module_constants: Vec<_> = constants
+                .iter()
+                .filter(|constant| in_module(constant, alias))
+                .collect();
+            let module_failures: Vec<_> = failures
+                .iter()
+                .filter(|failure| in_module(failure, alias))
+                .collect();
+            serde_json::json!({
+                "alias": alias,
+                "constants": module_constants,
+                "failures": module_failures,
+            })
+        })
+        .collect();
+
+    let harness_features: Vec<_> = HARNESS_FEATURES
+        .iter()
+        .filter(|feature| features.iter().any(|declared| declared == *feature))
         .collect();
 
     let build_statuses: Vec<_> = program_specs
@@ -95,27 +424,53 @@ pub fn generate(build_result: &ProgramBuildResult) -> Result<String, Error> {
     let rendered_content = template
         .render(context! {
             constants => constants,
+            failures => failures,
+            failure_marker => failure_marker,
+            alignment => alignment.get(),
+            modules => modules,
             build_statuses => build_statuses,
+            harness_features => harness_features,
         })
         .map_err(|e| {
             let msg = format!("Failed to render template: {}", e);
             Error::CodeGeneration(msg)
         })?;
 
-    Ok(rendered_content)
+    format_code(&rendered_content)
+}
+
+/// Format generated code in-process, so the output doesn't depend on rustfmt being installed
+///
+/// syn drops plain `//` comments, so the leading comment header is kept verbatim.
+fn format_code(code: &str) -> Result<String, Error> {
+    let (header, body) = split_header(code.trim_start());
+
+    let file = syn::parse_file(body).map_err(|e| {
+        let msg = format!("Generated code is not valid Rust: {}", e);
+        Error::CodeGeneration(msg)
+    })?;
+
+    Ok(format!("{}\n{}", header, prettyplease::unparse(&file)))
+}
+
+/// Split off the leading `//` comment header (but not `///` docs) of generated code
+pub fn split_header(code: &str) -> (&str, &str) {
+    let header_len = code
+        .lines()
+        .take_while(|line| line.starts_with("//") && !line.starts_with("///"))
+        .map(|line| line.len() + 1)
+        .sum::<usize>()
+        .min(code.len());
+    code.split_at(header_len)
 }
 
 /// Write generated code to OUT_DIR
-pub fn save(manifest_dir: &Path, code: &str) -> Result<(), Error> {
+pub fn save(code: &str) -> Result<(), Error> {
     // Generate to OUT_DIR instead of src/ (standard Rust pattern)
-    let out_dir = std::env::var("OUT_DIR").map_err(|_| {
-        Error::CodeGeneration(
-            "OUT_DIR not set - this should only be called from build scripts".to_string(),
-        )
-    })?;
-    let output_path = std::path::Path::new(&out_dir).join("generated.rs");
+    let output_path = out_dir()?.join("generated.rs");
 
-    fs::write(&output_path, code).map_err(|e| {
+    // Only touch the file when the code changes, so no-op builds don't recompile the crate
+    write_if_changed(&output_path, code.as_bytes()).map_err(|e| {
         let message = format!("Failed to write generated.rs: {}", e);
         Error::CodeGeneration(message)
     })?;
@@ -126,37 +481,22 @@ pub fn save(manifest_dir: &Path, code: &str) -> Result<(), Error> {
         output_path.display()
     );
 
-    // Format the generated code to prevent unexpected changes when users run cargo fmt
-    format_generated_code(manifest_dir, &output_path);
-
     Ok(())
 }
 
-/// Format generated code with cargo fmt, ignoring errors
-fn format_generated_code(manifest_dir: &Path, file_path: &Path) {
-    let result = Command::new("cargo")
-        .args(["fmt", "--manifest-path"])
-        .arg(manifest_dir.join("Cargo.toml"))
-        .arg("--")
-        .arg(file_path)
-        .output();
-
-    if let Err(e) = result {
-        eprintln!("Warning: Could not run cargo fmt: {}", e);
-    } else if let Ok(output) = result {
-        if !output.status.success() {
-            eprintln!(
-                "Warning: Failed to format generated code: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
-        }
-    }
+/// Build script output directory where generated files live
+pub fn out_dir() -> Result<PathBuf, Error> {
+    std::env::var("OUT_DIR").map(PathBuf::from).map_err(|_| {
+        Error::CodeGeneration(
+            "OUT_DIR not set - this should only be called from build scripts".to_string(),
+        )
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::programs::SolanaProgram;
+    use crate::programs::{BuildTarget, SolanaProgram};
     use std::path::PathBuf;
 
     fn sample_programs() -> Vec<SolanaProgram> {
@@ -164,12 +504,24 @@ mod tests {
             SolanaProgram {
                 package_name: "package1".to_string(),
                 target_name: "target1".to_string(),
+                build_target: BuildTarget::Lib("target1".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
                 manifest_path: PathBuf::from("/path/to/Cargo.toml"),
                 constant_name: "TARGET1_ELF".to_string(),
             },
             SolanaProgram {
                 package_name: "package2".to_string(),
                 target_name: "target2".to_string(),
+                build_target: BuildTarget::Lib("target2".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
                 manifest_path: PathBuf::from("/path/to/other/Cargo.toml"),
                 constant_name: "TARGET2_ELF".to_string(),
             },
@@ -178,15 +530,21 @@ mod tests {
 
     #[test]
     fn test_generate_empty_programs() {
-        let result = generate(&ProgramBuildResult {
-            successful: Vec::new(),
-            failed: Vec::new(),
-        })
+        let result = generate(
+            &ProgramBuildResult {
+                successful: Vec::new(),
+                failed: Vec::new(),
+            },
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
         .unwrap();
 
         // Should generate valid Rust code with empty elves function
-        assert!(result.contains("pub fn elves() -> Vec<(&'static str, &'static [u8])> {"));
-        assert!(result.contains("vec![\n    ]"));
+        assert!(result.contains("pub fn elves() -> Vec<Elf> {"));
+        assert!(result.contains("    vec![]\n"));
         assert!(result.contains("// This file is auto-generated by elf-magic"));
     }
 
@@ -195,26 +553,47 @@ mod tests {
         let programs = [SolanaProgram {
             package_name: "my_package".to_string(),
             target_name: "my_target".to_string(),
+            build_target: BuildTarget::Lib("my_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         }];
 
-        let result = generate(&ProgramBuildResult {
-            successful: programs
-                .iter()
-                .map(|p| (p.clone(), PathBuf::from("/path/to/Cargo.toml")))
-                .collect(),
-            failed: Vec::new(),
-        })
+        let result = generate(
+            &ProgramBuildResult {
+                successful: programs
+                    .iter()
+                    .map(|p| (p.clone(), PathBuf::from("/path/to/Cargo.toml")))
+                    .collect(),
+                failed: Vec::new(),
+            },
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
         .unwrap();
 
         // Check constant definition
+        assert!(result.contains("pub const MY_TARGET_ELF: &[u8] = {"));
+        assert!(result.contains("*include_bytes!(env!(\"MY_TARGET_ELF_PATH\"))"));
+
+        // Check accessor honors the override env var
+        assert!(result.contains("pub fn my_target_elf() -> Elf {"));
+        assert!(result.contains(
+            "load_elf(\n                \"ELF_MAGIC_OVERRIDE_MY_TARGET\",\n                MY_TARGET_ELF,\n            )"
+        ));
+        // Release builds ignore overrides
         assert!(result.contains(
-            "pub const MY_TARGET_ELF: &[u8] = include_bytes!(env!(\"MY_TARGET_ELF_PATH\"));"
+            "    #[cfg(debug_assertions)]\n    if let Some(path) = std::env::var_os(override_env_var) {"
         ));
 
         // Check elves function includes the program
-        assert!(result.contains("(\"my_target\", MY_TARGET_ELF),"));
+        assert!(result.contains("    vec![my_target_elf()]\n"));
 
         // Check doc comment
         assert!(result.contains("/// ELF binary for the my_target Solana program"));
@@ -223,13 +602,19 @@ mod tests {
     #[test]
     fn test_generate_multiple_programs() {
         let programs = sample_programs();
-        let result = generate(&ProgramBuildResult {
-            successful: programs
-                .iter()
-                .map(|p| (p.clone(), PathBuf::from("/path/to/Cargo.toml")))
-                .collect(),
-            failed: Vec::new(),
-        })
+        let result = generate(
+            &ProgramBuildResult {
+                successful: programs
+                    .iter()
+                    .map(|p| (p.clone(), PathBuf::from("/path/to/Cargo.toml")))
+                    .collect(),
+                failed: Vec::new(),
+            },
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
         .unwrap();
 
         // Should have both constants
@@ -241,8 +626,7 @@ mod tests {
         assert!(result.contains("env!(\"TARGET2_ELF_PATH\")"));
 
         // Should include both in elves function
-        assert!(result.contains("(\"target1\", TARGET1_ELF),"));
-        assert!(result.contains("(\"target2\", TARGET2_ELF),"));
+        assert!(result.contains("    vec![target1_elf(), target2_elf()]\n"));
 
         // Should have proper doc comments
         assert!(result.contains("/// ELF binary for the target1 Solana program"));
@@ -254,21 +638,34 @@ mod tests {
         let programs = [SolanaProgram {
             package_name: "my-special-package".to_string(),
             target_name: "my_target_name".to_string(),
+            build_target: BuildTarget::Lib("my_target_name".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/Cargo.toml"),
             constant_name: "MY_TARGET_NAME_ELF".to_string(),
         }];
 
-        let result = generate(&ProgramBuildResult {
-            successful: programs
-                .iter()
-                .map(|p| (p.clone(), PathBuf::from("/path/to/Cargo.toml")))
-                .collect(),
-            failed: Vec::new(),
-        })
+        let result = generate(
+            &ProgramBuildResult {
+                successful: programs
+                    .iter()
+                    .map(|p| (p.clone(), PathBuf::from("/path/to/Cargo.toml")))
+                    .collect(),
+                failed: Vec::new(),
+            },
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
         .unwrap();
 
         // Target name should be preserved as-is in program name
-        assert!(result.contains("(\"my_target_name\", MY_TARGET_NAME_ELF),"));
+        assert!(result.contains("name: \"my_target_name\","));
+        assert!(result.contains("    vec![my_target_name_elf()]\n"));
 
         // But constant and env var should follow their respective conventions
         assert!(result.contains("pub const MY_TARGET_NAME_ELF"));
@@ -278,13 +675,19 @@ mod tests {
     #[test]
     fn test_generated_code_is_valid_rust() {
         let programs = sample_programs();
-        let result = generate(&ProgramBuildResult {
-            successful: programs
-                .iter()
-                .map(|p| (p.clone(), PathBuf::from("/path/to/Cargo.toml")))
-                .collect(),
-            failed: Vec::new(),
-        })
+        let result = generate(
+            &ProgramBuildResult {
+                successful: programs
+                    .iter()
+                    .map(|p| (p.clone(), PathBuf::from("/path/to/Cargo.toml")))
+                    .collect(),
+                failed: Vec::new(),
+            },
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
         .unwrap();
 
         // Basic syntax checks
@@ -308,10 +711,16 @@ mod tests {
         // This is harder to test without breaking the template
         // But we can at least verify the function signature works
         let _programs: Vec<SolanaProgram> = vec![];
-        let result = generate(&ProgramBuildResult {
-            successful: Vec::new(),
-            failed: Vec::new(),
-        });
+        let result = generate(
+            &ProgramBuildResult {
+                successful: Vec::new(),
+                failed: Vec::new(),
+            },
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        );
         assert!(result.is_ok());
     }
 
@@ -320,6 +729,12 @@ mod tests {
         let successful_program = SolanaProgram {
             package_name: "good_package".to_string(),
             target_name: "good_program".to_string(),
+            build_target: BuildTarget::Lib("good_program".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/good/Cargo.toml"),
             constant_name: "GOOD_PROGRAM_ELF".to_string(),
         };
@@ -327,6 +742,12 @@ mod tests {
         let failed_program = SolanaProgram {
             package_name: "bad_package".to_string(),
             target_name: "bad_program".to_string(),
+            build_target: BuildTarget::Lib("bad_program".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/bad/Cargo.toml"),
             constant_name: "BAD_PROGRAM_ELF".to_string(),
         };
@@ -342,7 +763,14 @@ mod tests {
             )],
         };
 
-        let result = generate(&build_result).unwrap();
+        let result = generate(
+            &build_result,
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
+        .unwrap();
 
         // Should contain build status comments
         assert!(result.contains("// Build Status:"));
@@ -354,8 +782,78 @@ mod tests {
         assert!(!result.contains("pub const BAD_PROGRAM_ELF"));
 
         // Should only include successful program in elves function
-        assert!(result.contains("(\"good_program\", GOOD_PROGRAM_ELF),"));
-        assert!(!result.contains("(\"bad_program\""));
+        assert!(result.contains("    vec![good_program_elf()]\n"));
+        assert!(!result.contains("bad_program_elf()"));
+
+        // Structured status table
+        assert!(result.contains("(\"good_program\", Status::Success),"));
+        assert!(result.contains("\"Failed to build program bad_program: wasi crate contains multiple cdylib targets\",\n        ),"));
+        assert!(result.contains("pub fn failed_programs() -> Vec<(&'static str, &'static str)>"));
+    }
+
+    #[test]
+    fn test_generate_failure_markers() {
+        let failed_program = SolanaProgram {
+            package_name: "bad_package".to_string(),
+            target_name: "bad_program".to_string(),
+            build_target: BuildTarget::Lib("bad_program".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/path/to/bad/Cargo.toml"),
+            constant_name: "BAD_PROGRAM_ELF".to_string(),
+        };
+
+        let build_result = ProgramBuildResult {
+            successful: Vec::new(),
+            failed: vec![(
+                failed_program,
+                Error::ProgramBuild {
+                    program: "bad_program".to_string(),
+                    error: "error: \"quoted\"\nsecond line".to_string(),
+                },
+            )],
+        };
+
+        let error_literal =
+            r#""Failed to build program bad_program: error: \"quoted\"\nsecond line""#;
+
+        let result = generate(
+            &build_result,
+            &[],
+            FailureMarker::Deprecated,
+            Alignment::default(),
+            &[],
+        )
+        .unwrap();
+        assert!(result.contains(&format!("#[deprecated(\n    note = {}\n)]", error_literal)));
+        assert!(result.contains("pub const BAD_PROGRAM_ELF: &[u8] = &[];"));
+        assert!(!result.contains("compile_error!"));
+
+        let result = generate(
+            &build_result,
+            &[],
+            FailureMarker::CompileError,
+            Alignment::default(),
+            &[],
+        )
+        .unwrap();
+        assert!(result.contains(&format!("compile_error!({});", error_literal)));
+        assert!(!result.contains("pub const BAD_PROGRAM_ELF"));
+
+        let result = generate(
+            &build_result,
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
+        .unwrap();
+        assert!(result.contains("// ✗ bad_program - FAILED: Failed to build program bad_program: error: \"quoted\" second line"));
+        assert!(!result.contains("BAD_PROGRAM_ELF"));
+        assert!(!result.contains("compile_error!"));
     }
 
     #[test]
@@ -364,6 +862,12 @@ mod tests {
         let zebra_program = SolanaProgram {
             package_name: "zebra".to_string(),
             target_name: "zebra".to_string(),
+            build_target: BuildTarget::Lib("zebra".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/zebra/Cargo.toml"),
             constant_name: "ZEBRA_ELF".to_string(),
         };
@@ -371,6 +875,12 @@ mod tests {
         let alpha_program = SolanaProgram {
             package_name: "alpha".to_string(),
             target_name: "alpha".to_string(),
+            build_target: BuildTarget::Lib("alpha".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/alpha/Cargo.toml"),
             constant_name: "ALPHA_ELF".to_string(),
         };
@@ -378,6 +888,12 @@ mod tests {
         let beta_program = SolanaProgram {
             package_name: "beta".to_string(),
             target_name: "beta".to_string(),
+            build_target: BuildTarget::Lib("beta".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/beta/Cargo.toml"),
             constant_name: "BETA_ELF".to_string(),
         };
@@ -397,7 +913,14 @@ mod tests {
             )],
         };
 
-        let result = generate(&build_result).unwrap();
+        let result = generate(
+            &build_result,
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
+        .unwrap();
 
         // Find the build status section
         let build_status_start = result.find("// Build Status:").unwrap();
@@ -430,8 +953,8 @@ mod tests {
         let elves_section = result.find("pub fn elves()").unwrap();
         let elves_content = &result[elves_section..];
 
-        let beta_elves_pos = elves_content.find("(\"beta\", BETA_ELF)").unwrap();
-        let zebra_elves_pos = elves_content.find("(\"zebra\", ZEBRA_ELF)").unwrap();
+        let beta_elves_pos = elves_content.find("beta_elf(),").unwrap();
+        let zebra_elves_pos = elves_content.find("zebra_elf(),").unwrap();
 
         assert!(
             beta_elves_pos < zebra_elves_pos,
@@ -444,6 +967,12 @@ mod tests {
         let lower_program = SolanaProgram {
             package_name: "lowercase".to_string(),
             target_name: "lowercase".to_string(),
+            build_target: BuildTarget::Lib("lowercase".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/lowercase/Cargo.toml"),
             constant_name: "LOWERCASE_ELF".to_string(),
         };
@@ -451,6 +980,12 @@ mod tests {
         let upper_program = SolanaProgram {
             package_name: "UPPERCASE".to_string(),
             target_name: "UPPERCASE".to_string(),
+            build_target: BuildTarget::Lib("UPPERCASE".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/UPPERCASE/Cargo.toml"),
             constant_name: "UPPERCASE_ELF".to_string(),
         };
@@ -463,7 +998,14 @@ mod tests {
             failed: Vec::new(),
         };
 
-        let result = generate(&build_result).unwrap();
+        let result = generate(
+            &build_result,
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
+        .unwrap();
 
         // Build status should be sorted by target_name: UPPERCASE, lowercase (uppercase comes first in ASCII)
         let build_status_start = result.find("// Build Status:").unwrap();
@@ -486,4 +1028,274 @@ mod tests {
             "UPPERCASE_ELF should come before LOWERCASE_ELF"
         );
     }
+
+    #[test]
+    fn test_generate_namespaced_layout() {
+        let core_vault = SolanaProgram {
+            package_name: "vault".to_string(),
+            target_name: "vault".to_string(),
+            build_target: BuildTarget::Lib("vault".to_string()),
+            workspace_alias: Some("programs".to_string()),
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/repo/programs/vault/Cargo.toml"),
+            constant_name: "VAULT_ELF".to_string(),
+        };
+
+        let example_vault = SolanaProgram {
+            workspace_alias: Some("examples".to_string()),
+            manifest_path: PathBuf::from("/repo/examples/vault/Cargo.toml"),
+            ..core_vault.clone()
+        };
+
+        let build_result = ProgramBuildResult {
+            successful: vec![
+                (core_vault, PathBuf::from("/tmp/programs/vault.so")),
+                (example_vault, PathBuf::from("/tmp/examples/vault.so")),
+            ],
+            failed: Vec::new(),
+        };
+
+        let modules = vec!["programs".to_string(), "examples".to_string()];
+        let result = generate(
+            &build_result,
+            &modules,
+            FailureMarker::Omit,
+            Alignment::default(),
+            &[],
+        )
+        .unwrap();
+
+        // One module per workspace, each with its own constants and elves()
+        let programs_mod = result.find("pub mod programs {").unwrap();
+        let examples_mod = result.find("pub mod examples {").unwrap();
+        assert!(programs_mod < examples_mod);
+        assert_eq!(result.matches("pub const VAULT_ELF").count(), 2);
+        assert!(result.contains("env!(\"PROGRAMS_VAULT_ELF_PATH\")"));
+        assert!(result.contains("env!(\"EXAMPLES_VAULT_ELF_PATH\")"));
+        assert!(result.contains("name: \"examples::vault\","));
+
+        // Top-level aggregate
+        assert!(result.contains("elves.extend(programs::elves());"));
+        assert!(result.contains("elves.extend(examples::elves());"));
+        assert!(result.contains("// ✓ programs::vault - SUCCESS"));
+
+        // Harness variants stay unique across modules
+        assert!(result.contains("Program::ProgramsVault => super::programs::vault_elf(),"));
+        assert!(result.contains("Program::ExamplesVault => super::examples::vault_elf(),"));
+    }
+
+    #[test]
+    fn test_generate_harness_helpers() {
+        let token = SolanaProgram {
+            package_name: "token".to_string(),
+            target_name: "token_program".to_string(),
+            build_target: BuildTarget::Lib("token_program".to_string()),
+            workspace_alias: None,
+            program_id: Some("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string()),
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/repo/token/Cargo.toml"),
+            constant_name: "TOKEN_PROGRAM_ELF".to_string(),
+        };
+
+        let no_id = SolanaProgram {
+            target_name: "scratch".to_string(),
+            build_target: BuildTarget::Lib("scratch".to_string()),
+            program_id: None,
+            constant_name: "SCRATCH_ELF".to_string(),
+            ..token.clone()
+        };
+
+        let build_result = ProgramBuildResult {
+            successful: vec![
+                (token, PathBuf::from("/tmp/token.so")),
+                (no_id, PathBuf::from("/tmp/scratch.so")),
+            ],
+            failed: Vec::new(),
+        };
+
+        let features = vec![
+            "default".to_string(),
+            "litesvm".to_string(),
+            "mollusk".to_string(),
+            "solana-program-test".to_string(),
+        ];
+        let result = generate(
+            &build_result,
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &features,
+        )
+        .unwrap();
+
+        assert!(result.contains("pub mod harness {"));
+        assert!(result.contains("Program::TokenProgram => super::token_program_elf(),"));
+        assert!(result.contains("Some([\n                        6, 221, 246, 225,"));
+        assert!(result.contains("Program::Scratch => None,"));
+
+        // Names are literals, so asking for one never runs the override loader
+        assert!(result.contains("Program::TokenProgram => \"token_program\","));
+        assert!(result.contains("LiteSVM rejected the {} program"));
+
+        // Framework loaders are gated behind the consumer's cargo features
+        assert!(result.contains("#[cfg(feature = \"litesvm\")]"));
+        assert!(result.contains("#[cfg(feature = \"mollusk\")]"));
+        assert!(result.contains("#[cfg(feature = \"solana-program-test\")]"));
+        assert!(!result.contains("unexpected_cfgs"));
+
+        // Only for the features the crate declares, so every cfg is an expected one
+        let features = vec!["mollusk".to_string()];
+        let result = generate(
+            &build_result,
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &features,
+        )
+        .unwrap();
+        assert!(result.contains("pub fn mollusk_for("));
+        assert!(!result.contains("feature = \"litesvm\""));
+        assert!(!result.contains("feature = \"solana-program-test\""));
+    }
+
+    #[test]
+    fn test_misspelled_harness_features() {
+        let features: Vec<String> = ["lite-svm", "litesvm", "molusk", "devnet", "mollusk-svm"]
+            .iter()
+            .map(|feature| feature.to_string())
+            .collect();
+
+        assert_eq!(
+            misspelled_harness_features(&features),
+            vec![("lite-svm", "litesvm"), ("molusk", "mollusk")]
+        );
+    }
+
+    #[test]
+    fn test_format_code_keeps_header_comments() {
+        let code = "\n// Header\n//\n/// Doc\npub const X: &[u8] = &[1,2,3];\n";
+        let formatted = format_code(code).unwrap();
+
+        assert_eq!(
+            formatted,
+            "// Header\n//\n\n/// Doc\npub const X: &[u8] = &[1, 2, 3];\n"
+        );
+        assert_eq!(format_code(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_code_rejects_invalid_rust() {
+        let error = format_code("pub const X: = ;").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Generated code is not valid Rust"));
+    }
+
+    #[test]
+    fn test_generate_aligned_constants() {
+        let program = SolanaProgram {
+            package_name: "vault".to_string(),
+            target_name: "vault".to_string(),
+            build_target: BuildTarget::Lib("vault".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/repo/vault/Cargo.toml"),
+            constant_name: "VAULT_ELF".to_string(),
+        };
+
+        let build_result = ProgramBuildResult {
+            successful: vec![(program, PathBuf::from("/tmp/vault.so"))],
+            failed: Vec::new(),
+        };
+
+        let alignment = Alignment::try_from(16).unwrap();
+        let result = generate(&build_result, &[], FailureMarker::Omit, alignment, &[]).unwrap();
+
+        // Aligned wrapper, exposed through the same &[u8] type
+        assert!(result.contains("#[repr(C, align(16))]\nstruct AlignedBytes<B: ?Sized>(B);"));
+        assert!(result.contains("const ALIGNED: &AlignedBytes<[u8]> = &AlignedBytes("));
+        assert!(result.contains("&ALIGNED.0"));
+        assert!(result.contains("/// ELF binary for the vault Solana program, aligned to 16 bytes"));
+    }
+
+    /// Compiles the harness against the real frameworks; needs network access, so run it with
+    /// `make harness-check`
+    #[test]
+    #[ignore]
+    fn test_harness_compiles_against_frameworks() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let elf_path = temp_dir.path().join("token.so");
+        std::fs::write(&elf_path, b"\x7fELF").unwrap();
+
+        let token = SolanaProgram {
+            package_name: "token".to_string(),
+            target_name: "token_program".to_string(),
+            build_target: BuildTarget::Lib("token_program".to_string()),
+            workspace_alias: None,
+            program_id: Some("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string()),
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/repo/token/Cargo.toml"),
+            constant_name: "TOKEN_PROGRAM_ELF".to_string(),
+        };
+        let env_var = token.env_var_name();
+        let build_result = ProgramBuildResult {
+            successful: vec![(token, elf_path)],
+            failed: Vec::new(),
+        };
+        let features: Vec<String> = HARNESS_FEATURES.iter().map(|f| f.to_string()).collect();
+        let code = generate(
+            &build_result,
+            &[],
+            FailureMarker::Omit,
+            Alignment::default(),
+            &features,
+        )
+        .unwrap();
+
+        // The manifest the README asks an ELF crate to add
+        let manifest = r#"[package]
+name = "harness-check"
+version = "0.1.0"
+edition = "2021"
+
+[workspace]
+
+[features]
+litesvm = ["dep:litesvm"]
+mollusk = ["dep:mollusk-svm"]
+solana-program-test = ["dep:solana-program-test", "dep:solana-sdk"]
+
+[dependencies]
+litesvm = { version = "0.6", optional = true }
+mollusk-svm = { version = "0.1", optional = true }
+solana-program-test = { version = "2.2", optional = true }
+solana-sdk = { version = "2.2", optional = true }
+"#;
+        std::fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+        std::fs::write(temp_dir.path().join("Cargo.toml"), manifest).unwrap();
+        std::fs::write(
+            temp_dir.path().join("src/lib.rs"),
+            format!("#![deny(warnings)]\n{}", code),
+        )
+        .unwrap();
+
+        let elf_path = temp_dir.path().join("token.so");
+        let status = std::process::Command::new(env!("CARGO"))
+            .args(["check", "--all-features", "--manifest-path"])
+            .arg(temp_dir.path().join("Cargo.toml"))
+            .env(&env_var, &elf_path)
+            .status()
+            .unwrap();
+        assert!(status.success(), "generated harness failed to compile");
+    }
 }
@@ -1,75 +1,211 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::error::Error;
 
+/// Config file read next to the ELF crate's Cargo.toml
+pub const STANDALONE_CONFIG_FILE: &str = "elf-magic.toml";
+
+/// Checked-in JSON Schema for the config, at the repository root
+pub const SCHEMA_FILE: &str = "elf-magic.schema.json";
+
+/// Where editors fetch [`SCHEMA_FILE`] from
+pub const SCHEMA_URL: &str =
+    "https://raw.githubusercontent.com/levicook/elf-magic/main/elf-magic.schema.json";
+
 /// Configuration for elf-magic from package.metadata.elf-magic
 ///
 /// Clean three-mode system: Magic (default single workspace) vs Permissive (multi-workspace with excludes) vs Laser Eyes (multi-workspace with includes)
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-#[serde(tag = "mode", rename_all = "kebab-case")]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "mode", rename_all = "kebab-case", deny_unknown_fields)]
 pub enum Config {
+    /// Every program in the ELF crate's own workspace
     #[serde(rename = "magic")]
+    #[default]
     Magic, // No fields! Just "run cargo metadata here"
 
+    /// Only the programs each workspace's `only` patterns match
     #[serde(rename = "laser-eyes")]
     LaserEyes {
+        /// Workspaces to discover programs in
         workspaces: Vec<LaserEyesWorkspaceConfig>,
+        /// Narrow every workspace to programs matching one of these, as well as its `only`
+        #[serde(default)]
+        global_only: Vec<FilterPattern>,
+        /// Deny patterns applied to every workspace
+        #[serde(default)]
+        global_deny: Vec<FilterPattern>,
+        /// Defaults for every workspace's `build` table
+        #[serde(default)]
+        build: BuildSettings,
+        /// Constant names, keyed by manifest path (optionally `#target`)
         #[serde(default)]
         constants: HashMap<String, String>,
+        /// Target names, keyed by manifest path (optionally `#target`)
         #[serde(default)]
         targets: HashMap<String, String>,
+        /// Program IDs, keyed by manifest path (optionally `#target`)
+        #[serde(default)]
+        program_ids: HashMap<String, String>,
+        #[serde(default)]
+        layout: Layout,
+        #[serde(default)]
+        failure_marker: FailureMarker,
+        #[serde(default)]
+        alignment: Alignment,
+        #[serde(default)]
+        vendor: Option<VendorConfig>,
+        /// Fail the build when an `only` pattern matches no program
+        #[serde(default)]
+        strict_only: bool,
     },
 
+    /// Every program in the listed workspaces, minus what `deny` patterns drop
     #[serde(rename = "permissive")]
     Permissive {
+        /// Workspaces to discover programs in
         workspaces: Vec<PermissiveWorkspaceConfig>,
+        /// Deny patterns applied to every workspace
         #[serde(default)]
-        global_deny: Vec<String>,
+        global_deny: Vec<FilterPattern>,
+        /// Narrow every workspace to programs matching one of these, as well as any `only`
+        #[serde(default)]
+        global_only: Vec<FilterPattern>,
+        /// Defaults for every workspace's `build` table
+        #[serde(default)]
+        build: BuildSettings,
+        /// Constant names, keyed by manifest path (optionally `#target`)
         #[serde(default)]
         constants: HashMap<String, String>,
+        /// Target names, keyed by manifest path (optionally `#target`)
         #[serde(default)]
         targets: HashMap<String, String>,
+        /// Program IDs, keyed by manifest path (optionally `#target`)
+        #[serde(default)]
+        program_ids: HashMap<String, String>,
+        #[serde(default)]
+        layout: Layout,
+        #[serde(default)]
+        failure_marker: FailureMarker,
+        #[serde(default)]
+        alignment: Alignment,
+        #[serde(default)]
+        vendor: Option<VendorConfig>,
+        /// Fail the build when an `only` pattern matches no program
+        #[serde(default)]
+        strict_only: bool,
     },
 }
 
 impl Config {
+    /// Load the config for the ELF crate in `manifest_dir`
+    ///
+    /// Sources, from least to most specific: `[workspace.metadata.elf-magic]` in the enclosing
+    /// workspace root, an `elf-magic.toml` next to the crate's Cargo.toml, and the crate's own
+    /// `[package.metadata.elf-magic]`. See [`merge_config`] for how they combine.
     pub fn load(manifest_dir: &Path) -> Result<Self, Error> {
-        let manifest_path = manifest_dir.join("Cargo.toml");
-        let content = fs::read_to_string(&manifest_path).map_err(|e| {
-            let message = format!("Failed to read Cargo.toml: {}", e);
-            Error::Config(message)
-        })?;
+        let toml_value = read_toml(&manifest_dir.join("Cargo.toml"))?;
+
+        let mut sources = Vec::new();
+        if let Some((root_dir, root)) = find_workspace_root(manifest_dir, &toml_value) {
+            let inherited = root
+                .get("workspace")
+                .and_then(|w| w.get("metadata"))
+                .and_then(|m| m.get("elf-magic"));
+            if let Some(inherited) = inherited {
+                // Paths in the root's table are relative to the root, not to this crate
+                let mut inherited = to_json(inherited)?;
+                anchor_paths(&mut inherited, &root_dir);
+                sources.push(("workspace.metadata.elf-magic", inherited));
+            }
+        }
 
-        let toml_value: toml::Value = toml::from_str(&content).map_err(|e| {
-            let message = format!("Invalid TOML in Cargo.toml: {}", e);
-            Error::Config(message)
-        })?;
+        let standalone_path = manifest_dir.join(STANDALONE_CONFIG_FILE);
+        if standalone_path.is_file() {
+            let standalone = read_toml(&standalone_path)?;
+            sources.push((STANDALONE_CONFIG_FILE, to_json(&standalone)?));
+        }
 
-        // Extract package.metadata.elf-magic, default to Magic mode if not present
-        let metadata = toml_value
+        let package_config = toml_value
             .get("package")
             .and_then(|p| p.get("metadata"))
             .and_then(|m| m.get("elf-magic"));
+        if let Some(package_config) = package_config {
+            sources.push(("package.metadata.elf-magic", to_json(package_config)?));
+        }
+
+        // No config anywhere: Magic mode
+        let has_own_config = sources
+            .iter()
+            .any(|(origin, _)| *origin != "workspace.metadata.elf-magic");
+        let mut merged: Option<serde_json::Value> = None;
+        for (origin, source) in sources {
+            check_filter_patterns(&source, origin)?;
+            merged = Some(match merged {
+                Some(mut inherited) => {
+                    drop_shadowed_overrides(&mut inherited, &source, manifest_dir);
+                    merge_config(inherited, source)
+                }
+                None => source,
+            });
+        }
+        let Some(mut json_value) = merged else {
+            return Ok(Config::Magic);
+        };
+
+        // The mode can come from any source, so it's checked on the merged table. Without one,
+        // the root's table only holds defaults for crates that pick a mode of their own.
+        if json_value.get("mode").is_none() && !has_own_config {
+            return Ok(Config::Magic);
+        }
+        check_magic_keys(&json_value)?;
+
+        apply_shared_build(&mut json_value);
+        serde_json::from_value(json_value).map_err(|e| {
+            let message = format!("Invalid elf-magic config: {}", suggest_known_name(e));
+            Error::Config(message)
+        })
+    }
 
-        match metadata {
-            Some(config_value) => {
-                let json_value = serde_json::to_value(config_value).map_err(|e| {
-                    let message = format!("Failed to convert config: {}", e);
-                    Error::Config(message)
-                })?;
-                serde_json::from_value(json_value).map_err(|e| {
-                    let message = format!("Invalid elf-magic config: {}", e);
-                    Error::Config(message)
-                })
+    /// JSON Schema for the `elf-magic` config table, as published in [`SCHEMA_FILE`]
+    ///
+    /// Covers every shape the table takes: a complete config, one layered over another
+    /// source (which can leave out `mode` and required keys), and a program's own settings.
+    pub fn json_schema() -> serde_json::Value {
+        let mut generator = schemars::generate::SchemaSettings::draft2020_12().into_generator();
+        let config = generator.subschema_for::<Config>();
+        let program = generator.subschema_for::<ProgramMetadata>();
+        let mut defs = generator.take_definitions(true);
+
+        // Each mode's table, with nothing required
+        let mut layered = defs["Config"]["oneOf"].clone();
+        for variant in layered.as_array_mut().into_iter().flatten() {
+            if let Some(variant) = variant.as_object_mut() {
+                variant.remove("required");
             }
-            None => Ok(Config::Magic),
         }
+        defs.insert(
+            "LayeredConfig".to_string(),
+            serde_json::json!({
+                "description": "Settings layered over another config source, which can supply `mode` and required keys",
+                "anyOf": layered
+            }),
+        );
+
+        serde_json::json!({
+            "$schema": generator.settings().meta_schema,
+            "$id": SCHEMA_URL,
+            "title": "elf-magic",
+            "anyOf": [config, { "$ref": "#/$defs/LayeredConfig" }, program],
+            "$defs": defs
+        })
     }
 
     /// Get the mode name as a string
@@ -98,94 +234,1287 @@ impl Config {
             Config::Permissive { targets, .. } => targets.clone(),
         }
     }
+
+    /// Get the program ID overrides for this config
+    pub fn program_ids(&self) -> HashMap<String, String> {
+        match self {
+            Config::Magic => HashMap::new(),
+            Config::LaserEyes { program_ids, .. } => program_ids.clone(),
+            Config::Permissive { program_ids, .. } => program_ids.clone(),
+        }
+    }
+
+    /// Get the codegen layout for this config
+    pub fn layout(&self) -> Layout {
+        match self {
+            Config::Magic => Layout::Flat,
+            Config::LaserEyes { layout, .. } => *layout,
+            Config::Permissive { layout, .. } => *layout,
+        }
+    }
+
+    /// Get what the generated code emits in place of a failed program's constant
+    pub fn failure_marker(&self) -> FailureMarker {
+        match self {
+            Config::Magic => FailureMarker::Omit,
+            Config::LaserEyes { failure_marker, .. } => *failure_marker,
+            Config::Permissive { failure_marker, .. } => *failure_marker,
+        }
+    }
+
+    /// Get the alignment of the embedded ELF bytes
+    pub fn alignment(&self) -> Alignment {
+        match self {
+            Config::Magic => Alignment::default(),
+            Config::LaserEyes { alignment, .. } => *alignment,
+            Config::Permissive { alignment, .. } => *alignment,
+        }
+    }
+
+    /// Get the checked-in output settings for this config, if any
+    pub fn vendor(&self) -> Option<&VendorConfig> {
+        match self {
+            Config::Magic => None,
+            Config::LaserEyes { vendor, .. } => vendor.as_ref(),
+            Config::Permissive { vendor, .. } => vendor.as_ref(),
+        }
+    }
+
+    /// Whether an `only` pattern that matches nothing is an error rather than a warning
+    pub fn strict_only(&self) -> bool {
+        match self {
+            Config::Magic => false,
+            Config::LaserEyes { strict_only, .. } => *strict_only,
+            Config::Permissive { strict_only, .. } => *strict_only,
+        }
+    }
+}
+
+/// Layout of the generated code
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// All constants at the top level of the generated file
+    #[default]
+    Flat,
+    /// One `pub mod <alias>` per configured workspace, plus a top-level aggregate `elves()`
+    Namespaced,
+}
+
+/// What the generated code emits in place of a program that failed to build
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureMarker {
+    /// Leave the constant out; the failure is only recorded in `BUILD_STATUS`
+    #[default]
+    Omit,
+    /// An empty `#[deprecated]` constant whose note carries the build error
+    Deprecated,
+    /// A `compile_error!` carrying the build error, failing the consumer's build
+    CompileError,
+}
+
+/// Byte alignment of the embedded ELF bytes, so loaders can parse them in place
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "u32", into = "u32")]
+pub struct Alignment(u32);
+
+impl Alignment {
+    /// Largest alignment rustc accepts in `#[repr(align(N))]`
+    const MAX: u32 = 1 << 29;
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
 }
 
-impl Default for Config {
+impl Default for Alignment {
     fn default() -> Self {
-        Self::Magic
+        Self(8)
+    }
+}
+
+impl TryFrom<u32> for Alignment {
+    type Error = String;
+
+    fn try_from(alignment: u32) -> Result<Self, Self::Error> {
+        if alignment.is_power_of_two() && alignment <= Self::MAX {
+            Ok(Self(alignment))
+        } else {
+            Err(format!(
+                "alignment must be a power of two up to {}, got {}",
+                Self::MAX,
+                alignment
+            ))
+        }
+    }
+}
+
+impl From<Alignment> for u32 {
+    fn from(alignment: Alignment) -> Self {
+        alignment.0
+    }
+}
+
+impl JsonSchema for Alignment {
+    fn schema_name() -> Cow<'static, str> {
+        "Alignment".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let powers: Vec<u32> = (0..=Self::MAX.trailing_zeros()).map(|n| 1 << n).collect();
+        schemars::json_schema!({
+            "type": "integer",
+            "description": "Byte alignment of the embedded ELF bytes, a power of two up to 2^29",
+            "enum": powers
+        })
+    }
+}
+
+/// Checked-in copy of the generated code, for review in source control
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VendorConfig {
+    /// Where to write the generated code, relative to the ELF crate and under `src/`
+    pub path: PathBuf,
+    /// Fail the build when the checked-in files are out of date instead of rewriting them
+    #[serde(default)]
+    pub verify: bool,
+}
+
+/// How `cargo build-sbf` is run for every program in a workspace
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BuildSettings {
+    /// Cargo features to build the programs with
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Extra `cargo build-sbf` arguments, e.g. `["--arch", "v3"]`
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// Environment variables set for `cargo build-sbf`
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Platform tools version passed as `--tools-version`
+    #[serde(default)]
+    pub tools_version: Option<String>,
+    /// Cargo target directory, relative to the ELF crate
+    #[serde(default)]
+    pub target_dir: Option<PathBuf>,
+    /// Require an up-to-date Cargo.lock (`--locked`)
+    #[serde(default = "default_locked")]
+    pub locked: bool,
+    /// Require an up-to-date Cargo.lock and no network access (`--frozen`)
+    #[serde(default)]
+    pub frozen: bool,
+    /// Never access the network (`--offline`)
+    #[serde(default)]
+    pub offline: bool,
+    /// Run `cargo generate-lockfile` for a workspace without a Cargo.lock instead of failing
+    #[serde(default)]
+    pub generate_lockfile: bool,
+}
+
+fn default_locked() -> bool {
+    true
+}
+
+impl Default for BuildSettings {
+    fn default() -> Self {
+        Self {
+            features: Vec::new(),
+            extra_args: Vec::new(),
+            env: BTreeMap::new(),
+            tools_version: None,
+            target_dir: None,
+            locked: default_locked(),
+            frozen: false,
+            offline: false,
+            generate_lockfile: false,
+        }
+    }
+}
+
+impl BuildSettings {
+    /// Lockfile and network flags, passed to both `cargo metadata` and `cargo build-sbf`
+    pub fn cargo_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if self.frozen {
+            flags.push("--frozen".to_string());
+            return flags;
+        }
+        if self.locked {
+            flags.push("--locked".to_string());
+        }
+        if self.offline {
+            flags.push("--offline".to_string());
+        }
+        flags
+    }
+
+    /// Whether cargo may not create or update Cargo.lock
+    pub fn requires_lockfile(&self) -> bool {
+        self.locked || self.frozen
+    }
+
+    /// Layer a program's own settings over the workspace's
+    ///
+    /// The program's `features` and `tools_version` replace the workspace's when set, its
+    /// `env` wins key by key, and its `extra_args` come after the workspace's.
+    pub fn for_program(&self, program: &ProgramMetadata) -> Self {
+        let mut settings = self.clone();
+        if !program.features.is_empty() {
+            settings.features = program.features.clone();
+        }
+        if program.tools_version.is_some() {
+            settings.tools_version = program.tools_version.clone();
+        }
+        settings
+            .extra_args
+            .extend(program.extra_args.iter().cloned());
+        settings.env.extend(program.env.clone());
+        settings
+    }
+}
+
+/// A program's own `[package.metadata.elf-magic]`, merged under the consumer config
+///
+/// Lives next to the program, so it survives the crate being moved. Settings apply to
+/// every cdylib target of the package; the consumer's `constants`/`targets` still win.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProgramMetadata {
+    /// Force (`true`) or rule out (`false`) treating the package as a Solana program
+    #[serde(default)]
+    pub program: Option<bool>,
+    /// Constant name (default: `<TARGET>_ELF`)
+    #[serde(default)]
+    pub constant: Option<String>,
+    /// Target name used in generated code and patterns (default: the cargo target name)
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Cargo features to build the program with, instead of the workspace's
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Extra `cargo build-sbf` arguments, after the workspace's
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// Environment variables for `cargo build-sbf`, over the workspace's
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Platform tools version, instead of the workspace's
+    #[serde(default)]
+    pub tools_version: Option<String>,
+    /// Leave the program out of every consumer
+    #[serde(default)]
+    pub skip: bool,
+    /// Free-form labels
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ProgramMetadata {
+    /// Read the `elf-magic` table from a package's `package.metadata`
+    pub fn from_package_metadata(
+        metadata: &serde_json::Value,
+        manifest_path: &Path,
+    ) -> Result<Self, Error> {
+        match metadata.get("elf-magic") {
+            Some(table) => serde_json::from_value(table.clone()).map_err(|e| {
+                Error::Config(format!(
+                    "Invalid [package.metadata.elf-magic] in {}: {}",
+                    manifest_path.display(),
+                    suggest_known_name(e)
+                ))
+            }),
+            None => Ok(Self::default()),
+        }
     }
 }
 
 /// Configuration for a single workspace in laser-eyes mode
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(transform = exclude_alias)]
 pub struct LaserEyesWorkspaceConfig {
-    pub manifest_path: String,
-    pub only: Vec<String>,
+    /// Path or glob pattern of workspace manifests
+    #[serde(default)]
+    pub manifest_path: Option<String>,
+    /// Directory walked for independent workspace roots, instead of `manifest_path`
+    #[serde(default)]
+    pub scan: Option<String>,
+    /// Programs to include
+    pub only: Vec<FilterPattern>,
+    /// Dropped after `only` has narrowed the workspace
+    #[serde(default)]
+    #[serde(alias = "exclude")]
+    pub deny: Vec<FilterPattern>,
+    /// How the workspace's programs are built
+    #[serde(default)]
+    pub build: BuildSettings,
+    /// Module name in the namespaced layout (defaults to the workspace directory name)
+    #[serde(default)]
+    pub alias: Option<String>,
 }
 
 /// Configuration for a single workspace in permissive mode
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(transform = exclude_alias)]
 pub struct PermissiveWorkspaceConfig {
-    pub manifest_path: String,
+    /// Path or glob pattern of workspace manifests
+    #[serde(default)]
+    pub manifest_path: Option<String>,
+    /// Directory walked for independent workspace roots, instead of `manifest_path`
+    #[serde(default)]
+    pub scan: Option<String>,
+    /// Programs to leave out
     #[serde(default)]
     #[serde(alias = "exclude")]
-    pub deny: Vec<String>,
+    pub deny: Vec<FilterPattern>,
+    /// Narrows the workspace to matching programs before `deny` applies
+    #[serde(default)]
+    pub only: Option<Vec<FilterPattern>>,
+    /// How the workspace's programs are built
+    #[serde(default)]
+    pub build: BuildSettings,
+    /// Module name in the namespaced layout (defaults to the workspace directory name)
+    #[serde(default)]
+    pub alias: Option<String>,
+}
+
+/// A `deny`/`only`/`global_deny` entry, parsed and validated when the config loads
+///
+/// Either a single matcher like `"target:vault*"` or `"!tag:test"`, or an all-of/any-of
+/// group written as a table: `{ all = ["path:*/programs/*", "!target:test_*"] }`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged, try_from = "RawFilterPattern")]
+pub enum FilterPattern {
+    Matcher(Matcher),
+    All { all: Vec<FilterPattern> },
+    Any { any: Vec<FilterPattern> },
+}
+
+/// A filter pattern as written in the config, before validation
+#[derive(Deserialize)]
+#[serde(
+    untagged,
+    expecting = "a pattern string like \"target:vault*\", or an { all = [...] } / { any = [...] } table"
+)]
+enum RawFilterPattern {
+    Matcher(String),
+    All { all: Vec<RawFilterPattern> },
+    Any { any: Vec<RawFilterPattern> },
+}
+
+impl TryFrom<RawFilterPattern> for FilterPattern {
+    type Error = String;
+
+    fn try_from(raw: RawFilterPattern) -> Result<Self, Self::Error> {
+        let parse_all = |patterns: Vec<RawFilterPattern>| {
+            patterns
+                .into_iter()
+                .map(FilterPattern::try_from)
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        match raw {
+            RawFilterPattern::Matcher(source) => source.parse().map(FilterPattern::Matcher),
+            RawFilterPattern::All { all } => parse_all(all).map(|all| FilterPattern::All { all }),
+            RawFilterPattern::Any { any } => parse_all(any).map(|any| FilterPattern::Any { any }),
+        }
+    }
+}
+
+impl JsonSchema for FilterPattern {
+    fn schema_name() -> Cow<'static, str> {
+        "FilterPattern".into()
+    }
+
+    fn json_schema(generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        let group = |key: &str, generator: &mut schemars::SchemaGenerator| {
+            schemars::json_schema!({
+                "type": "object",
+                "properties": { key: { "type": "array", "items": generator.subschema_for::<Self>() } },
+                "required": [key],
+                "additionalProperties": false
+            })
+        };
+
+        schemars::json_schema!({
+            "anyOf": [
+                generator.subschema_for::<Matcher>(),
+                group("all", generator),
+                group("any", generator)
+            ]
+        })
+    }
+}
+
+impl std::str::FromStr for FilterPattern {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        source.parse().map(FilterPattern::Matcher)
+    }
+}
+
+impl std::fmt::Display for FilterPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (key, patterns) = match self {
+            FilterPattern::Matcher(matcher) => return write!(f, "{}", matcher.source),
+            FilterPattern::All { all } => ("all", all),
+            FilterPattern::Any { any } => ("any", any),
+        };
+
+        write!(f, "{{ {} = [", key)?;
+        for (index, pattern) in patterns.iter().enumerate() {
+            let separator = if index == 0 { "" } else { ", " };
+            match pattern {
+                FilterPattern::Matcher(matcher) => write!(f, "{}{:?}", separator, matcher.source)?,
+                group => write!(f, "{}{}", separator, group)?,
+            }
+        }
+        write!(f, "] }}")
+    }
+}
+
+impl PartialEq<&str> for FilterPattern {
+    fn eq(&self, other: &&str) -> bool {
+        matches!(self, FilterPattern::Matcher(matcher) if matcher.source == *other)
+    }
+}
+
+/// One `[!]<field>:<glob>` or `[!]<field>:regex:<expression>` matcher
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    pub negated: bool,
+    pub field: MatchField,
+    pub value: MatchValue,
+    source: String,
+}
+
+/// Which property of a program a matcher looks at
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MatchField {
+    Target,
+    Package,
+    Path,
+    Constant,
+    Tag,
+}
+
+impl MatchField {
+    const ALL: [(&'static str, MatchField); 5] = [
+        ("target:", MatchField::Target),
+        ("package:", MatchField::Package),
+        ("path:", MatchField::Path),
+        ("constant:", MatchField::Constant),
+        ("tag:", MatchField::Tag),
+    ];
+}
+
+#[derive(Debug, Clone)]
+pub enum MatchValue {
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
+}
+
+impl MatchValue {
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            MatchValue::Glob(pattern) => pattern.matches(text),
+            MatchValue::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+impl std::str::FromStr for MatchValue {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        match pattern.strip_prefix("regex:") {
+            Some(expression) => regex::Regex::new(expression)
+                .map(MatchValue::Regex)
+                .map_err(|e| format!("invalid regex '{}': {}", expression, e)),
+            None => glob::Pattern::new(pattern)
+                .map(MatchValue::Glob)
+                .map_err(|e| {
+                    let hint = if pattern.contains("**") {
+                        "; `**` only works as a whole path component, use `*` inside names"
+                    } else if pattern.contains('[') {
+                        "; write a literal `[` as `[[]`"
+                    } else {
+                        ""
+                    };
+                    format!("invalid glob '{}': {}{}", pattern, e, hint)
+                }),
+        }
+    }
+}
+
+impl std::str::FromStr for Matcher {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let (negated, pattern) = match source.strip_prefix('!') {
+            Some(pattern) => (true, pattern),
+            None => (false, source),
+        };
+        let invalid = |reason: String| format!("invalid pattern '{}': {}", source, reason);
+
+        let Some((field, value)) = MatchField::ALL
+            .iter()
+            .find_map(|(prefix, field)| Some((*field, pattern.strip_prefix(prefix)?)))
+        else {
+            return Err(invalid(unknown_prefix_hint(pattern, negated)));
+        };
+
+        if value.is_empty() {
+            return Err(invalid(format!(
+                "nothing to match after the prefix; did you mean '{}*'?",
+                source
+            )));
+        }
+
+        Ok(Matcher {
+            negated,
+            field,
+            value: value.parse().map_err(invalid)?,
+            source: source.to_string(),
+        })
+    }
+}
+
+fn read_toml(path: &Path) -> Result<toml::Value, Error> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let content = fs::read_to_string(path).map_err(|e| {
+        let message = format!("Failed to read {}: {}", file_name, e);
+        Error::Config(message)
+    })?;
+
+    toml::from_str(&content).map_err(|e| {
+        let message = format!("Invalid TOML in {}: {}", file_name, e);
+        Error::Config(message)
+    })
+}
+
+fn to_json(value: &toml::Value) -> Result<serde_json::Value, Error> {
+    serde_json::to_value(value).map_err(|e| {
+        let message = format!("Failed to convert config: {}", e);
+        Error::Config(message)
+    })
+}
+
+/// The workspace root of the crate in `manifest_dir`, found the way cargo finds it
+///
+/// `package.workspace` names the root outright. Otherwise it's the nearest manifest above
+/// with a `[workspace]` table whose `exclude` doesn't cover the crate.
+fn find_workspace_root(
+    manifest_dir: &Path,
+    manifest: &toml::Value,
+) -> Option<(PathBuf, toml::Value)> {
+    if manifest.get("workspace").is_some() {
+        return Some((manifest_dir.to_path_buf(), manifest.clone()));
+    }
+
+    let read_root = |dir: &Path| {
+        let manifest_path = dir.join("Cargo.toml");
+        if !manifest_path.is_file() {
+            return None;
+        }
+        let manifest = read_toml(&manifest_path).ok()?;
+        let workspace = manifest.get("workspace")?;
+        let exclude = workspace.get("exclude").and_then(|e| e.as_array());
+        let excluded = exclude
+            .into_iter()
+            .flatten()
+            .filter_map(|path| path.as_str())
+            .any(|path| manifest_dir.starts_with(dir.join(path)));
+        Some((dir.to_path_buf(), manifest, excluded))
+    };
+
+    let declared_root = manifest
+        .get("package")
+        .and_then(|p| p.get("workspace"))
+        .and_then(|w| w.as_str());
+    if let Some(declared_root) = declared_root {
+        let dir = manifest_dir.join(declared_root);
+        let dir = fs::canonicalize(&dir).unwrap_or(dir);
+        return read_root(&dir).map(|(dir, manifest, _)| (dir, manifest));
+    }
+
+    manifest_dir
+        .ancestors()
+        .skip(1)
+        .filter_map(read_root)
+        .find(|(_, _, excluded)| !excluded)
+        .map(|(dir, manifest, _)| (dir, manifest))
+}
+
+/// Cargo features declared in the `[features]` table of the crate in `manifest_dir`
+pub fn declared_features(manifest_dir: &Path) -> Result<Vec<String>, Error> {
+    let manifest = read_toml(&manifest_dir.join("Cargo.toml"))?;
+    let features = manifest.get("features").and_then(|f| f.as_table());
+    Ok(features
+        .into_iter()
+        .flatten()
+        .map(|(name, _)| name.clone())
+        .collect())
+}
+
+/// Config files besides the crate's own Cargo.toml that [`Config::load`] reads
+pub fn inherited_config_files(manifest_dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let workspace_root = read_toml(&manifest_dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| find_workspace_root(manifest_dir, &manifest));
+    if let Some((root_dir, _)) = workspace_root.filter(|(dir, _)| dir != manifest_dir) {
+        files.push(root_dir.join("Cargo.toml"));
+    }
+
+    let standalone_path = manifest_dir.join(STANDALONE_CONFIG_FILE);
+    if standalone_path.is_file() {
+        files.push(standalone_path);
+    }
+    files
+}
+
+/// Make the relative paths in an inherited config absolute, from the directory declaring them
+fn anchor_paths(config: &mut serde_json::Value, dir: &Path) {
+    let anchor = |value: &mut serde_json::Value| {
+        if let Some(path) = value.as_str() {
+            *value = dir.join(path).display().to_string().into();
+        }
+    };
+
+    for key in OVERRIDE_KEYS {
+        if let Some(overrides) = config.get_mut(key).and_then(|o| o.as_object_mut()) {
+            *overrides = std::mem::take(overrides)
+                .into_iter()
+                .map(|(key, value)| (dir.join(key).display().to_string(), value))
+                .collect();
+        }
+    }
+
+    if let Some(target_dir) = config.pointer_mut("/build/target_dir") {
+        anchor(target_dir);
+    }
+
+    let workspaces = config.get_mut("workspaces").and_then(|w| w.as_array_mut());
+    for workspace in workspaces.into_iter().flatten() {
+        for pointer in ["/manifest_path", "/scan", "/build/target_dir"] {
+            if let Some(path) = workspace.pointer_mut(pointer) {
+                anchor(path);
+            }
+        }
+    }
+}
+
+/// Config tables keyed by manifest path
+const OVERRIDE_KEYS: [&str; 3] = ["constants", "targets", "program_ids"];
+
+/// Drop inherited overrides that `local` sets again, for the same program but spelled differently
+fn drop_shadowed_overrides(
+    inherited: &mut serde_json::Value,
+    local: &serde_json::Value,
+    manifest_dir: &Path,
+) {
+    for key in OVERRIDE_KEYS {
+        let Some(local) = local.get(key).and_then(|o| o.as_object()) else {
+            continue;
+        };
+        let shadowed: HashSet<OverrideKey> = local
+            .keys()
+            .map(|key| OverrideKey::parse(key, manifest_dir))
+            .collect();
+        if let Some(inherited) = inherited.get_mut(key).and_then(|o| o.as_object_mut()) {
+            inherited.retain(|key, _| !shadowed.contains(&OverrideKey::parse(key, manifest_dir)));
+        }
+    }
+}
+
+/// Layer a more specific config source over an inherited one
+///
+/// Tables merge key by key, with the more specific source winning (so `constants` and
+/// `build` settings can be overridden one entry at a time). `global_deny` and `global_only`
+/// lists add to the inherited ones. Any other value, including the `workspaces` list,
+/// replaces what was inherited.
+fn merge_config(inherited: serde_json::Value, local: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    let (mut merged, local) = match (inherited, local) {
+        (Value::Object(inherited), Value::Object(local)) => (inherited, local),
+        (_, local) => return local,
+    };
+
+    for (key, value) in local {
+        let value = match (key.as_str(), merged.remove(&key), value) {
+            (
+                "global_deny" | "global_only",
+                Some(Value::Array(mut inherited)),
+                Value::Array(local),
+            ) => {
+                inherited.extend(local);
+                Value::Array(inherited)
+            }
+            (_, Some(inherited), value) => merge_config(inherited, value),
+            (_, None, value) => value,
+        };
+        merged.insert(key, value);
+    }
+
+    Value::Object(merged)
+}
+
+/// Put the top-level `build` table under each workspace entry's own
+fn apply_shared_build(config: &mut serde_json::Value) {
+    let Some(shared) = config.get("build").cloned() else {
+        return;
+    };
+
+    let workspaces = config.get_mut("workspaces").and_then(|w| w.as_array_mut());
+    for workspace in workspaces.into_iter().flatten() {
+        if let Some(workspace) = workspace.as_object_mut() {
+            let build = match workspace.remove("build") {
+                Some(local) => merge_config(shared.clone(), local),
+                None => shared.clone(),
+            };
+            workspace.insert("build".to_string(), build);
+        }
+    }
+}
+
+/// Parse every filter pattern up front, so an invalid one is reported with its key path
+fn check_filter_patterns(config: &serde_json::Value, origin: &str) -> Result<(), Error> {
+    let mut lists: Vec<_> = ["global_deny", "global_only"]
+        .into_iter()
+        .map(|key| (key.to_string(), config.get(key)))
+        .collect();
+    let workspaces = config.get("workspaces").and_then(|w| w.as_array());
+    for (index, workspace) in workspaces.into_iter().flatten().enumerate() {
+        for key in ["deny", "exclude", "only"] {
+            lists.push((format!("workspaces[{}].{}", index, key), workspace.get(key)));
+        }
+    }
+
+    for (key, patterns) in lists {
+        let patterns = patterns.and_then(|p| p.as_array());
+        for (index, pattern) in patterns.into_iter().flatten().enumerate() {
+            FilterPattern::deserialize(pattern).map_err(|e| {
+                Error::Config(format!(
+                    "Invalid elf-magic config at {}.{}[{}]: {}",
+                    origin, key, index, e
+                ))
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reject settings next to `mode = "magic"`, which serde would silently drop from the unit variant
+///
+/// Runs on the merged config, so settings inherited from another source count too.
+fn check_magic_keys(config: &serde_json::Value) -> Result<(), Error> {
+    if config.get("mode").and_then(|m| m.as_str()) != Some("magic") {
+        return Ok(());
+    }
+
+    let mut keys = config.as_object().into_iter().flatten().map(|(key, _)| key);
+    match keys.find(|key| *key != "mode") {
+        Some(key) => Err(Error::Config(format!(
+            "Invalid elf-magic config: unknown field `{}`; magic mode takes no other settings, inherited or not",
+            key
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Explain a missing or misspelled matcher prefix, suggesting the closest valid one
+fn unknown_prefix_hint(pattern: &str, negated: bool) -> String {
+    let bang = if negated { "!" } else { "" };
+    let prefixes = MatchField::ALL.map(|(prefix, _)| prefix).join(", ");
+
+    let Some((prefix, rest)) = pattern.split_once(':') else {
+        return format!(
+            "missing a prefix ({}); did you mean '{}target:{}'?",
+            prefixes, bang, pattern
+        );
+    };
+
+    if prefix == "regex" {
+        return format!(
+            "`regex:` goes after a prefix; did you mean '{}target:{}'?",
+            bang, pattern
+        );
+    }
+
+    let known_fields = MatchField::ALL.map(|(known, _)| known.trim_end_matches(':'));
+    match closest_match(prefix, known_fields) {
+        Some(known) => format!(
+            "unknown prefix '{}:'; did you mean '{}{}:{}'?",
+            prefix, bang, known, rest
+        ),
+        None => format!("unknown prefix '{}:'; use one of {}", prefix, prefixes),
+    }
+}
+
+/// Replace serde's list of expected keys with the closest one, when there is a likely typo
+///
+/// `deny_unknown_fields` reports `unknown field `global_denny`, expected one of ...`;
+/// this turns it into `unknown field `global_denny`; did you mean `global_deny`?`. Unknown
+/// `mode` values get the same treatment.
+fn suggest_known_name(error: serde_json::Error) -> String {
+    let message = error.to_string();
+    let Some((unknown, expected)) = ["unknown field `", "unknown variant `"]
+        .iter()
+        .find_map(|prefix| message.strip_prefix(prefix))
+        .and_then(|rest| rest.split_once("`, expected "))
+    else {
+        return message;
+    };
+
+    // Expected names are the backticked words: `a`, `b` or `c` / one of `a`, `b`, `c`
+    let known = expected.split('`').skip(1).step_by(2);
+    match closest_match(unknown, known) {
+        Some(known) => format!(
+            "{}`; did you mean `{}`?",
+            &message[..message.len() - expected.len() - "`, expected ".len()],
+            known
+        ),
+        None => message,
+    }
+}
+
+/// The known name within two edits of `input`, if any
+pub(crate) fn closest_match<'a>(
+    input: &str,
+    known: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    known
+        .into_iter()
+        .map(|known| (strsim::levenshtein(input, known), known))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+/// Accept the `exclude` alias of a workspace's `deny` in the schema, as serde does
+fn exclude_alias(schema: &mut schemars::Schema) {
+    let deny = schema.pointer("/properties/deny").cloned();
+    let properties = schema
+        .get_mut("properties")
+        .and_then(|properties| properties.as_object_mut());
+    if let (Some(properties), Some(mut deny)) = (properties, deny) {
+        deny["description"] = "Alias of `deny`".into();
+        properties.insert("exclude".to_string(), deny);
+    }
+}
+
+impl PartialEq for Matcher {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Matcher {}
+
+impl JsonSchema for Matcher {
+    fn schema_name() -> Cow<'static, str> {
+        "Matcher".into()
+    }
+
+    fn json_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": "string",
+            "description": "`[!]<field>:<glob>` or `[!]<field>:regex:<expression>`, where the field is target, package, path, constant or tag",
+            "pattern": "^!?(target|package|path|constant|tag):.+"
+        })
+    }
+}
+
+impl Serialize for Matcher {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+/// Resolved key of a `constants`/`targets`/`program_ids` override
+///
+/// Keys are manifest paths relative to the config file, optionally narrowed to a
+/// single cargo target with a `#target_name` suffix (e.g. `programs/multi/Cargo.toml#vault`).
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct OverrideKey {
+    pub manifest_path: PathBuf,
+    pub target: Option<String>,
+}
+
+impl OverrideKey {
+    fn parse(key: &str, config_file_dir: &Path) -> Self {
+        let (relative_path, target) = match key.rsplit_once('#') {
+            Some((path, target)) => (path, Some(target.to_string())),
+            None => (key, None),
+        };
+
+        Self {
+            manifest_path: resolve_config_path(config_file_dir, relative_path),
+            target,
+        }
+    }
+}
+
+/// Resolve a path written in the config against the directory of the manifest declaring it
+///
+/// Existing paths are canonicalized, so they compare equal to what cargo metadata reports
+/// however they were spelled (`../`, `./`, symlinks). Paths that don't exist yet only have
+/// `.` and `..` folded.
+pub fn resolve_config_path(config_file_dir: &Path, path: impl AsRef<Path>) -> PathBuf {
+    let joined = config_file_dir.join(path);
+    fs::canonicalize(&joined).unwrap_or_else(|_| {
+        let mut normalized = PathBuf::new();
+        for component in joined.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                component => normalized.push(component),
+            }
+        }
+        normalized
+    })
+}
+
+/// Look up an override for a cargo target, preferring a target-specific key over a manifest-wide one
+pub fn lookup_override<'a>(
+    overrides: &'a HashMap<OverrideKey, String>,
+    manifest_path: &Path,
+    target_name: &str,
+) -> Option<&'a String> {
+    let target_key = OverrideKey {
+        manifest_path: manifest_path.to_path_buf(),
+        target: Some(target_name.to_string()),
+    };
+    let manifest_key = OverrideKey {
+        manifest_path: manifest_path.to_path_buf(),
+        target: None,
+    };
+
+    overrides
+        .get(&target_key)
+        .or_else(|| overrides.get(&manifest_key))
 }
 
 /// Resolve constant override paths to absolute paths based on config file location
 pub fn resolve_constants_paths(
     constants: &HashMap<String, String>,
     config_file_dir: &Path,
-) -> HashMap<PathBuf, String> {
+) -> HashMap<OverrideKey, String> {
     let mut resolved = HashMap::new();
 
-    for (relative_path, constant_name) in constants {
-        let absolute_path = config_file_dir.join(relative_path);
-        resolved.insert(absolute_path, constant_name.clone());
+    for (key, constant_name) in constants {
+        resolved.insert(
+            OverrideKey::parse(key, config_file_dir),
+            constant_name.clone(),
+        );
+    }
+
+    resolved
+}
+
+/// Resolve target override paths to absolute paths based on config file location
+pub fn resolve_targets_paths(
+    targets: &HashMap<String, String>,
+    config_file_dir: &Path,
+) -> HashMap<OverrideKey, String> {
+    let mut resolved = HashMap::new();
+
+    for (key, target_name) in targets {
+        resolved.insert(
+            OverrideKey::parse(key, config_file_dir),
+            target_name.clone(),
+        );
+    }
+
+    resolved
+}
+
+/// Resolve program ID override paths to absolute paths based on config file location
+pub fn resolve_program_ids_paths(
+    program_ids: &HashMap<String, String>,
+    config_file_dir: &Path,
+) -> HashMap<OverrideKey, String> {
+    let mut resolved = HashMap::new();
+
+    for (key, program_id) in program_ids {
+        resolved.insert(OverrideKey::parse(key, config_file_dir), program_id.clone());
+    }
+
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn pattern(source: &str) -> FilterPattern {
+        source.parse().unwrap()
+    }
+
+    fn create_temp_manifest(content: &str) -> (TempDir, PathBuf) {
+        let temp_dir = TempDir::new().unwrap();
+        let manifest_path = temp_dir.path().join("Cargo.toml");
+        fs::write(&manifest_path, content).unwrap();
+        let path = temp_dir.path().to_path_buf();
+        (temp_dir, path)
+    }
+
+    #[test]
+    fn test_load_config_magic_mode_default() {
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+
+        match config {
+            Config::Magic => {}
+            _ => panic!("Expected Magic mode"),
+        }
+    }
+
+    #[test]
+    fn test_load_config_magic_mode_explicit() {
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "magic"
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+
+        match config {
+            Config::Magic => {}
+            _ => panic!("Expected Magic mode"),
+        }
+    }
+
+    #[test]
+    fn test_load_config_workspace_globs_and_scan() {
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "permissive"
+workspaces = [
+    { manifest_path = "examples/*/Cargo.toml", deny = ["target:test*"] },
+    { scan = "vendor" }
+]
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+
+        match config {
+            Config::Permissive { workspaces, .. } => {
+                assert_eq!(
+                    workspaces[0].manifest_path.as_deref(),
+                    Some("examples/*/Cargo.toml")
+                );
+                assert_eq!(workspaces[0].scan, None);
+                assert_eq!(workspaces[1].manifest_path, None);
+                assert_eq!(workspaces[1].scan.as_deref(), Some("vendor"));
+            }
+            _ => panic!("Expected Permissive mode"),
+        }
+    }
+
+    #[test]
+    fn test_load_config_composite_patterns() {
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "laser-eyes"
+workspaces = [
+    { manifest_path = "./Cargo.toml", only = [
+        "tag:core",
+        { all = ["path:*/programs/*", "!target:test_*"] },
+        { any = ["target:regex:^vault_v\\d+$", { all = ["constant:SWAP_*"] }] },
+    ] }
+]
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+
+        match config {
+            Config::LaserEyes { workspaces, .. } => {
+                assert_eq!(
+                    workspaces[0].only,
+                    vec![
+                        pattern("tag:core"),
+                        FilterPattern::All {
+                            all: vec![pattern("path:*/programs/*"), pattern("!target:test_*")],
+                        },
+                        FilterPattern::Any {
+                            any: vec![
+                                pattern(r"target:regex:^vault_v\d+$"),
+                                FilterPattern::All {
+                                    all: vec![pattern("constant:SWAP_*")],
+                                },
+                            ],
+                        },
+                    ]
+                );
+            }
+            _ => panic!("Expected LaserEyes mode"),
+        }
     }
 
-    resolved
-}
+    fn load_error(elf_magic_table: &str) -> String {
+        let manifest_content = format!(
+            "[package]\nname = \"test-package\"\nversion = \"0.1.0\"\n\n[package.metadata.elf-magic]\n{}",
+            elf_magic_table
+        );
+        let (_temp_dir, manifest_dir) = create_temp_manifest(&manifest_content);
+        Config::load(&manifest_dir).unwrap_err().to_string()
+    }
 
-/// Resolve target override paths to absolute paths based on config file location
-pub fn resolve_targets_paths(
-    targets: &HashMap<String, String>,
-    config_file_dir: &Path,
-) -> HashMap<PathBuf, String> {
-    let mut resolved = HashMap::new();
+    #[test]
+    fn test_load_config_rejects_invalid_patterns() {
+        let error = load_error(
+            r#"mode = "permissive"
+workspaces = [{ manifest_path = "./Cargo.toml", deny = ["target:test*", "targt:vault"] }]"#,
+        );
+        assert!(error.contains("package.metadata.elf-magic.workspaces[0].deny[1]"));
+        assert!(error.contains("did you mean 'target:vault'?"));
+
+        let error = load_error(
+            r#"mode = "permissive"
+global_deny = ["test*"]
+workspaces = [{ manifest_path = "./Cargo.toml" }]"#,
+        );
+        assert!(error.contains("package.metadata.elf-magic.global_deny[0]"));
+        assert!(error.contains("did you mean 'target:test*'?"));
+
+        let error = load_error(
+            r#"mode = "laser-eyes"
+workspaces = [{ manifest_path = "./Cargo.toml", only = [{ all = ["path:*/programs/*", "!target:[test"] }] }]"#,
+        );
+        assert!(error.contains("package.metadata.elf-magic.workspaces[0].only[0]"));
+        assert!(error.contains("invalid glob '[test'"));
+        assert!(error.contains("`[[]`"));
+
+        let error = load_error(
+            r#"mode = "laser-eyes"
+workspaces = [{ manifest_path = "./Cargo.toml", only = ["regex:^vault"] }]"#,
+        );
+        assert!(error.contains("did you mean 'target:regex:^vault'?"));
+
+        let error = load_error(
+            r#"mode = "laser-eyes"
+global_only = ["tag:core", "tga:main"]
+workspaces = [{ manifest_path = "./Cargo.toml", only = [] }]"#,
+        );
+        assert!(error.contains("package.metadata.elf-magic.global_only[1]"));
+        assert!(error.contains("did you mean 'tag:main'?"));
+    }
 
-    for (relative_path, target_name) in targets {
-        let absolute_path = config_file_dir.join(relative_path);
-        resolved.insert(absolute_path, target_name.clone());
+    #[test]
+    fn test_parse_filter_patterns() {
+        let matcher = match pattern("!tag:regex:^core$") {
+            FilterPattern::Matcher(matcher) => matcher,
+            other => panic!("Expected a matcher, got {:?}", other),
+        };
+        assert!(matcher.negated);
+        assert_eq!(matcher.field, MatchField::Tag);
+        assert!(matcher.value.matches("core"));
+        assert!(!matcher.value.matches("core-test"));
+
+        let error = "target:".parse::<FilterPattern>().unwrap_err();
+        assert!(error.contains("did you mean 'target:*'?"));
+
+        let error = "package:regex:(".parse::<FilterPattern>().unwrap_err();
+        assert!(error.contains("invalid regex '('"));
+
+        let error = "version:1.*".parse::<FilterPattern>().unwrap_err();
+        assert!(error.contains("unknown prefix 'version:'; use one of target:, package:"));
     }
 
-    resolved
-}
+    #[test]
+    fn test_load_config_strict_only() {
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::TempDir;
+[package.metadata.elf-magic]
+mode = "laser-eyes"
+strict_only = true
+workspaces = [{ manifest_path = "./Cargo.toml", only = ["target:vault"] }]
+"#;
 
-    fn create_temp_manifest(content: &str) -> (TempDir, PathBuf) {
-        let temp_dir = TempDir::new().unwrap();
-        let manifest_path = temp_dir.path().join("Cargo.toml");
-        fs::write(&manifest_path, content).unwrap();
-        let path = temp_dir.path().to_path_buf();
-        (temp_dir, path)
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+        assert!(config.strict_only());
+        assert!(!Config::Magic.strict_only());
     }
 
     #[test]
-    fn test_load_config_magic_mode_default() {
+    fn test_load_config_only_and_deny_together() {
         let manifest_content = r#"
 [package]
 name = "test-package"
 version = "0.1.0"
 edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "permissive"
+strict_only = true
+global_only = ["path:*/programs/*"]
+workspaces = [
+    { manifest_path = "./Cargo.toml", only = ["target:vault*"], deny = ["target:*_test"] },
+    { manifest_path = "examples/Cargo.toml" }
+]
 "#;
 
         let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
         let config = Config::load(&manifest_dir).unwrap();
-
+        assert!(config.strict_only());
         match config {
-            Config::Magic => {}
-            _ => panic!("Expected Magic mode"),
+            Config::Permissive {
+                workspaces,
+                global_only,
+                ..
+            } => {
+                assert_eq!(global_only, vec!["path:*/programs/*"]);
+                assert_eq!(workspaces[0].only, Some(vec![pattern("target:vault*")]));
+                assert_eq!(workspaces[0].deny, vec!["target:*_test"]);
+                assert_eq!(workspaces[1].only, None);
+            }
+            _ => panic!("Expected Permissive config"),
         }
-    }
 
-    #[test]
-    fn test_load_config_magic_mode_explicit() {
         let manifest_content = r#"
 [package]
 name = "test-package"
@@ -193,15 +1522,24 @@ version = "0.1.0"
 edition = "2021"
 
 [package.metadata.elf-magic]
-mode = "magic"
+mode = "laser-eyes"
+global_deny = ["tag:experimental"]
+workspaces = [{ manifest_path = "./Cargo.toml", only = ["path:*/programs/*"], exclude = ["target:*_test"] }]
 "#;
 
         let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
-        let config = Config::load(&manifest_dir).unwrap();
-
-        match config {
-            Config::Magic => {}
-            _ => panic!("Expected Magic mode"),
+        match Config::load(&manifest_dir).unwrap() {
+            Config::LaserEyes {
+                workspaces,
+                global_deny,
+                global_only,
+                ..
+            } => {
+                assert_eq!(global_deny, vec!["tag:experimental"]);
+                assert!(global_only.is_empty());
+                assert_eq!(workspaces[0].deny, vec!["target:*_test"]);
+            }
+            _ => panic!("Expected LaserEyes config"),
         }
     }
 
@@ -230,11 +1568,15 @@ workspaces = [
                 global_deny,
                 constants,
                 targets,
+                ..
             } => {
                 assert_eq!(workspaces.len(), 2);
-                assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+                assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
                 assert_eq!(workspaces[0].deny.len(), 0);
-                assert_eq!(workspaces[1].manifest_path, "examples/basic/Cargo.toml");
+                assert_eq!(
+                    workspaces[1].manifest_path.as_deref(),
+                    Some("examples/basic/Cargo.toml")
+                );
                 assert_eq!(workspaces[1].deny, vec!["target:test*"]);
                 assert_eq!(global_deny.len(), 0); // No global excludes in this test
                 assert!(constants.is_empty());
@@ -268,9 +1610,10 @@ workspaces = [
                 global_deny,
                 constants,
                 targets,
+                ..
             } => {
                 assert_eq!(workspaces.len(), 1);
-                assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+                assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
                 assert_eq!(workspaces[0].deny, vec!["target:test*"]);
                 assert_eq!(global_deny.len(), 0);
                 assert!(constants.is_empty());
@@ -327,6 +1670,106 @@ mode = "invalid-mode"
         assert!(error_message.contains("Invalid elf-magic config"));
     }
 
+    #[test]
+    fn test_load_config_rejects_unknown_keys() {
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "permissive"
+workspaces = [{ manifest_path = "./Cargo.toml", exclude = ["target:test*"] }]
+global_deny = ["target:bench*"]
+"#;
+
+        let load_error = |content: &str| {
+            let (_temp_dir, manifest_dir) = create_temp_manifest(content);
+            Config::load(&manifest_dir).unwrap_err().to_string()
+        };
+
+        // Deny's `exclude` alias is still accepted
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        assert!(Config::load(&manifest_dir).is_ok());
+
+        let content = manifest_content.replace("global_deny", "global_denny");
+        assert_eq!(
+            load_error(&content),
+            "Failed to parse config: Invalid elf-magic config: unknown field `global_denny`; did you mean `global_deny`?"
+        );
+
+        let content = manifest_content.replace("workspaces =", "workspace =");
+        assert!(
+            load_error(&content).contains("unknown field `workspace`; did you mean `workspaces`?")
+        );
+
+        // Nested tables are checked too
+        let content = manifest_content.replace("exclude =", "buidl = { featrues = [] }, exclude =");
+        assert!(load_error(&content).contains("unknown field `buidl`; did you mean `build`?"));
+
+        let content = manifest_content.replace("mode = \"permissive\"", "mode = \"laser_eyes\"");
+        assert!(load_error(&content)
+            .contains("unknown variant `laser_eyes`; did you mean `laser-eyes`?"));
+
+        // Nothing close: serde's list of expected keys stays
+        let content = manifest_content.replace("global_deny", "unrelated");
+        assert!(load_error(&content)
+            .contains("unknown field `unrelated`, expected one of `workspaces`"));
+
+        let content = manifest_content.replace("mode = \"permissive\"", "mode = \"magic\"");
+        assert!(load_error(&content)
+            .contains("unknown field `global_deny`; magic mode takes no other settings"));
+    }
+
+    #[test]
+    fn test_checked_in_schema_is_up_to_date() {
+        let schema_path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SCHEMA_FILE);
+        let schema = serde_json::to_string_pretty(&Config::json_schema()).unwrap() + "\n";
+        if std::env::var_os("ELF_MAGIC_UPDATE_SCHEMA").is_some() {
+            fs::write(&schema_path, &schema).unwrap();
+        }
+
+        let checked_in = fs::read_to_string(&schema_path).unwrap_or_default();
+        assert!(
+            checked_in == schema,
+            "{} is out of date; regenerate it with `make schema`",
+            SCHEMA_FILE
+        );
+    }
+
+    #[test]
+    fn test_schema_covers_every_table_shape() {
+        let schema = Config::json_schema();
+        assert_eq!(
+            schema["anyOf"],
+            serde_json::json!([
+                { "$ref": "#/$defs/Config" },
+                { "$ref": "#/$defs/LayeredConfig" },
+                { "$ref": "#/$defs/ProgramMetadata" }
+            ])
+        );
+
+        // A layered table like `{ global_deny = [...] }` needs neither `mode` nor `workspaces`
+        let defs = &schema["$defs"];
+        let layered = defs["LayeredConfig"]["anyOf"].as_array().unwrap();
+        assert_eq!(layered.len(), 3);
+        assert!(layered
+            .iter()
+            .all(|variant| variant.get("required").is_none()));
+        assert!(defs["Config"]["oneOf"][1]["required"].is_array());
+
+        // Program-side tables are closed, like the consumer's
+        assert_eq!(defs["ProgramMetadata"]["additionalProperties"], false);
+        assert!(defs["ProgramMetadata"]["properties"]["tags"].is_object());
+
+        let alignments = defs["Alignment"]["enum"].as_array().unwrap();
+        assert!(alignments.contains(&8.into()));
+        assert!(alignments.contains(&(1u32 << 29).into()));
+        assert!(!alignments.contains(&12.into()));
+        assert!(!alignments.contains(&(1u32 << 30).into()));
+    }
+
     #[test]
     fn test_config_default() {
         let config = Config::default();
@@ -360,6 +1803,7 @@ workspaces = [
                 global_deny,
                 constants,
                 targets,
+                ..
             } => {
                 assert_eq!(workspaces[0].deny.len(), 0); // Should default to empty
                 assert_eq!(global_deny.len(), 0); // Should default to empty
@@ -396,6 +1840,7 @@ workspaces = [
                 global_deny,
                 constants,
                 targets,
+                ..
             } => {
                 assert_eq!(workspaces.len(), 2);
                 assert_eq!(
@@ -404,11 +1849,14 @@ workspaces = [
                 );
 
                 // First workspace has no local excludes
-                assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+                assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
                 assert_eq!(workspaces[0].deny.len(), 0);
 
                 // Second workspace has local excludes
-                assert_eq!(workspaces[1].manifest_path, "examples/escrow/Cargo.toml");
+                assert_eq!(
+                    workspaces[1].manifest_path.as_deref(),
+                    Some("examples/escrow/Cargo.toml")
+                );
                 assert_eq!(workspaces[1].deny, vec!["target:test*"]);
 
                 assert!(constants.is_empty());
@@ -442,18 +1890,22 @@ workspaces = [
                 workspaces,
                 constants,
                 targets,
+                ..
             } => {
                 assert_eq!(workspaces.len(), 2);
 
                 // First workspace
-                assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+                assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
                 assert_eq!(
                     workspaces[0].only,
                     vec!["target:token_manager", "target:governance"]
                 );
 
                 // Second workspace
-                assert_eq!(workspaces[1].manifest_path, "examples/defi/Cargo.toml");
+                assert_eq!(
+                    workspaces[1].manifest_path.as_deref(),
+                    Some("examples/defi/Cargo.toml")
+                );
                 assert_eq!(
                     workspaces[1].only,
                     vec!["target:swap*", "package:my-*-program"]
@@ -489,9 +1941,10 @@ workspaces = [
                 workspaces,
                 constants,
                 targets,
+                ..
             } => {
                 assert_eq!(workspaces.len(), 1);
-                assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+                assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
                 assert_eq!(workspaces[0].only, vec!["target:my_program"]);
                 assert!(constants.is_empty());
                 assert!(targets.is_empty());
@@ -523,9 +1976,10 @@ workspaces = [
                 workspaces,
                 constants,
                 targets,
+                ..
             } => {
                 assert_eq!(workspaces.len(), 1);
-                assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+                assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
                 assert_eq!(workspaces[0].only.len(), 0);
                 assert!(constants.is_empty());
                 assert!(targets.is_empty());
@@ -559,9 +2013,13 @@ targets = { "upstream/programs/token/p-token" = "potato" }
                 workspaces,
                 constants,
                 targets,
+                ..
             } => {
                 assert_eq!(workspaces.len(), 1);
-                assert_eq!(workspaces[0].manifest_path, "./upstream/Cargo.toml");
+                assert_eq!(
+                    workspaces[0].manifest_path.as_deref(),
+                    Some("./upstream/Cargo.toml")
+                );
                 assert_eq!(
                     workspaces[0].only,
                     vec!["path:*/program/*", "path:*/p-token/*"]
@@ -588,4 +2046,505 @@ targets = { "upstream/programs/token/p-token" = "potato" }
             _ => panic!("Expected LaserEyes mode"),
         }
     }
+
+    #[test]
+    fn test_resolve_constants_paths_manifest_and_target_keys() {
+        let constants = HashMap::from([
+            (
+                "programs/multi/Cargo.toml".to_string(),
+                "MULTI_ELF".to_string(),
+            ),
+            (
+                "programs/multi/Cargo.toml#vault".to_string(),
+                "MULTI_VAULT_ELF".to_string(),
+            ),
+        ]);
+
+        let resolved = resolve_constants_paths(&constants, Path::new("/repo/elves"));
+        let manifest_path = Path::new("/repo/elves/programs/multi/Cargo.toml");
+
+        assert_eq!(
+            lookup_override(&resolved, manifest_path, "vault"),
+            Some(&"MULTI_VAULT_ELF".to_string())
+        );
+        assert_eq!(
+            lookup_override(&resolved, manifest_path, "escrow"),
+            Some(&"MULTI_ELF".to_string())
+        );
+        assert_eq!(
+            lookup_override(
+                &resolved,
+                Path::new("/repo/elves/other/Cargo.toml"),
+                "vault"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_config_from_standalone_file() {
+        let (temp_dir, manifest_dir) = create_temp_manifest(
+            "[package]\nname = \"elves\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+        );
+        fs::write(
+            temp_dir.path().join(STANDALONE_CONFIG_FILE),
+            "mode = \"laser-eyes\"\nworkspaces = [{ manifest_path = \"./Cargo.toml\", only = [\"target:vault\"] }]\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&manifest_dir).unwrap();
+        assert_eq!(config.mode_name(), "laser-eyes");
+
+        // The package's own table is layered on top
+        fs::write(
+            manifest_dir.join("Cargo.toml"),
+            "[package]\nname = \"elves\"\nversion = \"0.1.0\"\n\n[package.metadata.elf-magic]\nstrict_only = true\n",
+        )
+        .unwrap();
+        let config = Config::load(&manifest_dir).unwrap();
+        assert_eq!(config.mode_name(), "laser-eyes");
+        assert!(config.strict_only());
+    }
+
+    #[test]
+    fn test_load_config_inherits_from_workspace_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("Cargo.toml"),
+            r#"
+[workspace]
+members = ["elves"]
+
+[workspace.metadata.elf-magic]
+mode = "permissive"
+global_deny = ["tag:experimental"]
+constants = { "programs/vault/Cargo.toml" = "VAULT_ELF", "programs/escrow/Cargo.toml" = "ESCROW_ELF" }
+build = { tools_version = "v1.43", env = { RUST_LOG = "warn" } }
+workspaces = [{ manifest_path = "./Cargo.toml" }]
+"#,
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("elves")).unwrap();
+        fs::write(
+            root.join("elves/Cargo.toml"),
+            r#"
+[package]
+name = "elves"
+version = "0.1.0"
+
+[package.metadata.elf-magic]
+global_deny = ["target:*_test"]
+constants = { "../programs/escrow/Cargo.toml" = "ESCROW_V2_ELF" }
+build = { env = { RUST_LOG = "debug" } }
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&root.join("elves")).unwrap();
+        let Config::Permissive {
+            workspaces,
+            global_deny,
+            constants,
+            ..
+        } = &config
+        else {
+            panic!("Expected Permissive config");
+        };
+
+        // Deny lists add up, inherited first
+        assert_eq!(global_deny, &vec!["tag:experimental", "target:*_test"]);
+
+        // Inherited paths are relative to the workspace root
+        assert_eq!(
+            workspaces[0].manifest_path,
+            Some(root.join("./Cargo.toml").display().to_string())
+        );
+        let resolved = resolve_constants_paths(constants, &root.join("elves"));
+        let vault = resolve_config_path(root, "programs/vault/Cargo.toml");
+        let escrow = resolve_config_path(root, "programs/escrow/Cargo.toml");
+        assert_eq!(
+            lookup_override(&resolved, &vault, "vault"),
+            Some(&"VAULT_ELF".to_string())
+        );
+        assert_eq!(
+            lookup_override(&resolved, &escrow, "escrow"),
+            Some(&"ESCROW_V2_ELF".to_string())
+        );
+
+        // The shared build table lands in every workspace, merged key by key
+        let build = &workspaces[0].build;
+        assert_eq!(build.tools_version.as_deref(), Some("v1.43"));
+        assert_eq!(build.env["RUST_LOG"], "debug");
+    }
+
+    #[test]
+    fn test_load_config_finds_workspace_root_like_cargo() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("Cargo.toml"),
+            r#"
+[workspace]
+members = ["programs/*"]
+exclude = ["tools"]
+
+[workspace.metadata.elf-magic]
+mode = "permissive"
+workspaces = [{ manifest_path = "./Cargo.toml" }]
+"#,
+        )
+        .unwrap();
+        let elves_manifest = "[package]\nname = \"elves\"\nversion = \"0.1.0\"\n";
+
+        // A crate the repo workspace excludes doesn't inherit its config
+        fs::create_dir_all(root.join("tools/elves")).unwrap();
+        fs::write(root.join("tools/elves/Cargo.toml"), elves_manifest).unwrap();
+        assert_eq!(
+            Config::load(&root.join("tools/elves")).unwrap(),
+            Config::Magic
+        );
+        assert!(inherited_config_files(&root.join("tools/elves")).is_empty());
+
+        // Unless it points at the root itself
+        fs::write(
+            root.join("tools/elves/Cargo.toml"),
+            format!("{}workspace = \"../..\"\n", elves_manifest),
+        )
+        .unwrap();
+        let config = Config::load(&root.join("tools/elves")).unwrap();
+        assert_eq!(config.mode_name(), "permissive");
+
+        // Inside an excluded directory, a workspace of its own is still inherited from
+        fs::create_dir_all(root.join("tools/nested/elves")).unwrap();
+        fs::write(
+            root.join("tools/nested/Cargo.toml"),
+            "[workspace]\nmembers = [\"elves\"]\n\n[workspace.metadata.elf-magic]\nmode = \"permissive\"\nworkspaces = []\n",
+        )
+        .unwrap();
+        fs::write(root.join("tools/nested/elves/Cargo.toml"), elves_manifest).unwrap();
+        let config = Config::load(&root.join("tools/nested/elves")).unwrap();
+        assert_eq!(config.mode_name(), "permissive");
+    }
+
+    #[test]
+    fn test_load_config_mode_is_checked_on_merged_sources() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let write_root = |table: &str| {
+            fs::write(
+                root.join("Cargo.toml"),
+                format!(
+                    "[workspace]\nmembers = [\"elves\"]\n\n[workspace.metadata.elf-magic]\n{}",
+                    table
+                ),
+            )
+            .unwrap();
+        };
+        fs::create_dir_all(root.join("elves")).unwrap();
+        let write_elves = |table: &str| {
+            fs::write(
+                root.join("elves/Cargo.toml"),
+                format!(
+                    "[package]\nname = \"elves\"\nversion = \"0.1.0\"\n{}",
+                    table
+                ),
+            )
+            .unwrap();
+        };
+
+        // Shared settings alone don't pick a mode for a crate without config of its own
+        write_root("global_deny = [\"tag:experimental\"]\n");
+        write_elves("");
+        let config = Config::load(&root.join("elves")).unwrap();
+        assert_eq!(config, Config::Magic);
+
+        // Choosing magic over inherited settings would drop them
+        write_root(
+            "mode = \"permissive\"\nglobal_deny = [\"tag:experimental\"]\nworkspaces = [{ manifest_path = \"./Cargo.toml\" }]\n",
+        );
+        write_elves("\n[package.metadata.elf-magic]\nmode = \"magic\"\n");
+        let error = Config::load(&root.join("elves")).unwrap_err().to_string();
+        assert!(
+            error.contains("unknown field `global_deny`; magic mode takes no other settings"),
+            "{}",
+            error
+        );
+
+        // A mode set by the root applies to a crate that adds settings of its own
+        write_elves("\n[package.metadata.elf-magic]\nstrict_only = true\n");
+        let config = Config::load(&root.join("elves")).unwrap();
+        assert_eq!(config.mode_name(), "permissive");
+        assert!(config.strict_only());
+    }
+
+    #[test]
+    fn test_load_config_reports_pattern_errors_by_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"elves\"]\n\n[workspace.metadata.elf-magic]\nglobal_deny = [\"tset*\"]\n",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("elves")).unwrap();
+        fs::write(
+            root.join("elves/Cargo.toml"),
+            "[package]\nname = \"elves\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let error = Config::load(&root.join("elves")).unwrap_err().to_string();
+        assert!(error.contains("at workspace.metadata.elf-magic.global_deny[0]"));
+    }
+
+    #[test]
+    fn test_resolve_config_path_matches_cargo_paths() {
+        // Paths that don't exist are folded lexically
+        assert_eq!(
+            resolve_config_path(Path::new("/repo/elves"), "../programs/./vault/Cargo.toml"),
+            PathBuf::from("/repo/programs/vault/Cargo.toml")
+        );
+
+        // Existing ones are canonicalized, however the config spelled them
+        let temp_dir = TempDir::new().unwrap();
+        let root = fs::canonicalize(temp_dir.path()).unwrap();
+        fs::create_dir_all(root.join("elves")).unwrap();
+        fs::create_dir_all(root.join("programs/vault")).unwrap();
+        fs::write(root.join("programs/vault/Cargo.toml"), "").unwrap();
+
+        let constants = HashMap::from([(
+            "../programs/vault/Cargo.toml".to_string(),
+            "VAULT_V2_ELF".to_string(),
+        )]);
+        let resolved = resolve_constants_paths(&constants, &root.join("elves"));
+        assert_eq!(
+            lookup_override(&resolved, &root.join("programs/vault/Cargo.toml"), "vault"),
+            Some(&"VAULT_V2_ELF".to_string())
+        );
+    }
+
+    #[test]
+    fn test_load_config_namespaced_layout_with_aliases() {
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "permissive"
+layout = "namespaced"
+workspaces = [
+    { manifest_path = "./Cargo.toml", alias = "programs" },
+    { manifest_path = "examples/basic/Cargo.toml" }
+]
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+
+        assert_eq!(config.layout(), Layout::Namespaced);
+        match config {
+            Config::Permissive { workspaces, .. } => {
+                assert_eq!(workspaces[0].alias, Some("programs".to_string()));
+                assert_eq!(workspaces[1].alias, None);
+            }
+            _ => panic!("Expected Permissive mode"),
+        }
+    }
+
+    #[test]
+    fn test_layout_defaults_to_flat() {
+        assert_eq!(Config::Magic.layout(), Layout::Flat);
+
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "laser-eyes"
+workspaces = [
+    { manifest_path = "./Cargo.toml", only = ["target:my_program"] }
+]
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+
+        assert_eq!(config.layout(), Layout::Flat);
+    }
+
+    #[test]
+    fn test_load_config_failure_marker() {
+        assert_eq!(Config::Magic.failure_marker(), FailureMarker::Omit);
+
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "permissive"
+failure_marker = "compile-error"
+workspaces = [{ manifest_path = "./Cargo.toml" }]
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+
+        assert_eq!(config.failure_marker(), FailureMarker::CompileError);
+    }
+
+    #[test]
+    fn test_load_config_vendor() {
+        assert_eq!(Config::Magic.vendor(), None);
+
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "laser-eyes"
+workspaces = [{ manifest_path = "./Cargo.toml", only = ["target:vault"] }]
+vendor = { path = "src/elves.rs" }
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+
+        assert_eq!(
+            config.vendor(),
+            Some(&VendorConfig {
+                path: PathBuf::from("src/elves.rs"),
+                verify: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_config_alignment() {
+        assert_eq!(Config::Magic.alignment().get(), 8);
+
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "permissive"
+alignment = 16
+workspaces = [{ manifest_path = "./Cargo.toml" }]
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        let config = Config::load(&manifest_dir).unwrap();
+        assert_eq!(config.alignment().get(), 16);
+
+        let manifest_content = manifest_content.replace("alignment = 16", "alignment = 12");
+        let (_temp_dir, manifest_dir) = create_temp_manifest(&manifest_content);
+        let error = Config::load(&manifest_dir).unwrap_err().to_string();
+        assert!(error.contains("alignment must be a power of two"));
+    }
+
+    #[test]
+    fn test_load_config_workspace_build_settings() {
+        let manifest_content = r#"
+[package]
+name = "test-package"
+version = "0.1.0"
+edition = "2021"
+
+[package.metadata.elf-magic]
+mode = "laser-eyes"
+workspaces = [
+    { manifest_path = "./Cargo.toml", only = ["target:*"] },
+    { manifest_path = "examples/Cargo.toml", only = ["target:*"], build = { features = ["devnet"], extra_args = ["--arch", "v3"], env = { RUST_LOG = "debug" }, tools_version = "v1.43", target_dir = "../target/examples", locked = false, offline = true } }
+]
+"#;
+
+        let (_temp_dir, manifest_dir) = create_temp_manifest(manifest_content);
+        match Config::load(&manifest_dir).unwrap() {
+            Config::LaserEyes { workspaces, .. } => {
+                assert_eq!(workspaces[0].build, BuildSettings::default());
+                assert_eq!(workspaces[0].build.cargo_flags(), vec!["--locked"]);
+
+                let build = &workspaces[1].build;
+                assert_eq!(build.features, vec!["devnet"]);
+                assert_eq!(build.extra_args, vec!["--arch", "v3"]);
+                assert_eq!(build.env["RUST_LOG"], "debug");
+                assert_eq!(build.tools_version.as_deref(), Some("v1.43"));
+                assert_eq!(build.target_dir, Some(PathBuf::from("../target/examples")));
+                assert_eq!(build.cargo_flags(), vec!["--offline"]);
+                assert!(!build.requires_lockfile());
+            }
+            _ => panic!("Expected LaserEyes config"),
+        }
+    }
+
+    #[test]
+    fn test_declared_features() {
+        let (_temp_dir, manifest_dir) = create_temp_manifest(
+            "[package]\nname = \"elves\"\nversion = \"0.1.0\"\n\n[features]\nlitesvm = [\"dep:litesvm\"]\ndevnet = []\n",
+        );
+        assert_eq!(
+            declared_features(&manifest_dir).unwrap(),
+            vec!["devnet", "litesvm"]
+        );
+
+        let (_temp_dir, manifest_dir) =
+            create_temp_manifest("[package]\nname = \"elves\"\nversion = \"0.1.0\"\n");
+        assert!(declared_features(&manifest_dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_program_metadata_from_package_metadata() {
+        let manifest_path = Path::new("/repo/vault/Cargo.toml");
+        let metadata = serde_json::json!({
+            "elf-magic": {
+                "constant": "VAULT_V2_ELF",
+                "target": "vault_v2",
+                "features": ["devnet"],
+                "tags": ["core"],
+            },
+            "docs.rs": { "all-features": true },
+        });
+
+        let program = ProgramMetadata::from_package_metadata(&metadata, manifest_path).unwrap();
+        assert_eq!(program.constant.as_deref(), Some("VAULT_V2_ELF"));
+        assert_eq!(program.target.as_deref(), Some("vault_v2"));
+        assert_eq!(program.features, vec!["devnet"]);
+        assert_eq!(program.tags, vec!["core"]);
+        assert_eq!(program.program, None);
+        assert!(!program.skip);
+
+        // No table, no settings
+        let program =
+            ProgramMetadata::from_package_metadata(&serde_json::Value::Null, manifest_path)
+                .unwrap();
+        assert_eq!(program, ProgramMetadata::default());
+
+        let metadata = serde_json::json!({ "elf-magic": { "skip": "yes" } });
+        let error = ProgramMetadata::from_package_metadata(&metadata, manifest_path)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Invalid [package.metadata.elf-magic] in /repo/vault/Cargo.toml"));
+
+        // Unknown keys are rejected, with the closest known one suggested
+        let metadata = serde_json::json!({ "elf-magic": { "constnat": "VAULT_ELF" } });
+        let error = ProgramMetadata::from_package_metadata(&metadata, manifest_path)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("unknown field `constnat`; did you mean `constant`?"),
+            "{}",
+            error
+        );
+    }
 }
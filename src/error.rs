@@ -13,6 +13,12 @@ pub enum Error {
     #[error("Failed to generate code: {0}")]
     CodeGeneration(String),
 
+    #[error("Name collision between programs: {0}")]
+    NameCollision(String),
+
+    #[error("Checked-in generated code is out of date: {0}")]
+    VendorDrift(String),
+
     #[error(transparent)]
     Metadata(#[from] cargo_metadata::Error),
 }
@@ -60,6 +66,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_name_collision_error_display() {
+        let error = Error::NameCollision("constant `VAULT_ELF`".to_string());
+        assert_eq!(
+            error.to_string(),
+            "Name collision between programs: constant `VAULT_ELF`"
+        );
+    }
+
+    #[test]
+    fn test_vendor_drift_error_display() {
+        let error = Error::VendorDrift("src/elves.rs differs".to_string());
+        assert_eq!(
+            error.to_string(),
+            "Checked-in generated code is out of date: src/elves.rs differs"
+        );
+    }
+
     #[test]
     fn test_metadata_error_conversion() {
         let metadata_error = cargo_metadata::Error::CargoMetadata {
@@ -2,7 +2,9 @@ mod builder;
 mod codegen;
 pub mod config;
 mod error;
+mod manifest;
 mod programs;
+mod vendor;
 mod workspace;
 
 use std::{env, path::PathBuf};
@@ -10,7 +12,11 @@ use std::{env, path::PathBuf};
 use crate::{
     config::Config,
     error::Error,
-    programs::{deduplicate_programs, BuildResults, SolanaProgram},
+    manifest::BuildManifest,
+    programs::{
+        check_unused_only_patterns, deduplicate_programs, detect_name_collisions, BuildResults,
+        SolanaProgram,
+    },
 };
 
 #[deprecated(note = "use build() instead")]
@@ -31,6 +37,9 @@ pub fn build() -> Result<BuildResults, Error> {
         .iter()
         .map(|w| w.discover_programs())
         .collect::<Result<Vec<_>, _>>()?;
+    if config.strict_only() {
+        check_unused_only_patterns(&discovered_programs)?;
+    }
 
     // Extract and deduplicate included programs
     let included_programs: Vec<SolanaProgram> = discovered_programs
@@ -38,11 +47,31 @@ pub fn build() -> Result<BuildResults, Error> {
         .flat_map(|w| w.included.iter().cloned())
         .collect();
     let included_programs = deduplicate_programs(included_programs);
+    detect_name_collisions(&included_programs)?;
 
     // Build, generate, and save
     let build_result = builder::build_programs(&included_programs);
-    let code = codegen::generate(&build_result)?;
-    codegen::save(&cargo_manifest_dir, &code)?;
+    let workspace_modules: Vec<String> =
+        workspaces.iter().filter_map(|w| w.alias.clone()).collect();
+    let features = config::declared_features(&cargo_manifest_dir)?;
+    for (feature, known) in codegen::misspelled_harness_features(&features) {
+        println!(
+            "cargo:warning=elf-magic: feature `{}` doesn't enable a harness loader; did you mean `{}`?",
+            feature, known
+        );
+    }
+    let code = codegen::generate(
+        &build_result,
+        &workspace_modules,
+        config.failure_marker(),
+        config.alignment(),
+        &features,
+    )?;
+    codegen::save(&code)?;
+    if let Some(vendor) = config.vendor() {
+        vendor::sync(&cargo_manifest_dir, vendor, &code, &build_result)?;
+    }
+    BuildManifest::new(config.mode_name(), &discovered_programs, &build_result)?.save()?;
 
     builder::enable_incremental_builds(&cargo_manifest_dir, &included_programs)?;
 
@@ -0,0 +1,331 @@
+use std::{fs, path::PathBuf};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    builder::write_if_changed,
+    codegen,
+    error::Error,
+    programs::{Decision, DiscoveredPrograms, ProgramBuildResult, SolanaProgram},
+};
+
+/// File name of the machine-readable build manifest written next to generated.rs
+pub const MANIFEST_FILE_NAME: &str = "elf-magic.json";
+
+/// Machine-readable record of everything a build discovered, built, and embedded
+#[derive(Debug, Serialize)]
+pub struct BuildManifest {
+    pub elf_magic_version: String,
+    pub mode: String,
+    pub workspaces: Vec<WorkspaceEntry>,
+}
+
+/// Programs discovered in a single workspace
+#[derive(Debug, Serialize)]
+pub struct WorkspaceEntry {
+    pub manifest_path: String,
+    pub alias: Option<String>,
+    pub programs: Vec<ProgramEntry>,
+}
+
+/// What happened to a single discovered program
+#[derive(Debug, Serialize)]
+pub struct ProgramEntry {
+    pub target_name: String,
+    pub package_name: String,
+    pub manifest_path: PathBuf,
+    pub constant_name: String,
+    pub env_var: String,
+    pub status: ProgramStatus,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Why the filter included or excluded the program
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decision: Option<Decision>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub so_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of a discovered program
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProgramStatus {
+    /// Built and embedded in generated.rs
+    Built,
+    /// Included but `cargo build-sbf` failed
+    Failed,
+    /// Filtered out by the config
+    Excluded,
+    /// A cdylib left out before filtering (see `reason`)
+    Skipped,
+}
+
+impl BuildManifest {
+    pub fn new(
+        mode: &str,
+        discovered_programs: &[DiscoveredPrograms],
+        build_result: &ProgramBuildResult,
+    ) -> Result<Self, Error> {
+        let mut workspaces = Vec::new();
+
+        for discovered in discovered_programs {
+            let mut programs = Vec::new();
+
+            for program in &discovered.included {
+                programs.push(included_entry(program, build_result)?);
+            }
+
+            for program in &discovered.excluded {
+                programs.push(ProgramEntry::new(program, ProgramStatus::Excluded));
+            }
+
+            for (program, reason) in &discovered.skipped {
+                let mut entry = ProgramEntry::new(program, ProgramStatus::Skipped);
+                entry.reason = Some(reason.to_string());
+                programs.push(entry);
+            }
+
+            workspaces.push(WorkspaceEntry {
+                manifest_path: discovered.workspace_path.clone(),
+                alias: discovered.workspace_alias.clone(),
+                programs,
+            });
+        }
+
+        Ok(Self {
+            elf_magic_version: env!("CARGO_PKG_VERSION").to_string(),
+            mode: mode.to_string(),
+            workspaces,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|e| {
+            let message = format!("Failed to serialize {}: {}", MANIFEST_FILE_NAME, e);
+            Error::CodeGeneration(message)
+        })
+    }
+
+    /// Write the manifest to OUT_DIR next to generated.rs
+    pub fn save(&self) -> Result<(), Error> {
+        let output_path = codegen::out_dir()?.join(MANIFEST_FILE_NAME);
+
+        write_if_changed(&output_path, self.to_json()?.as_bytes()).map_err(|e| {
+            let message = format!("Failed to write {}: {}", MANIFEST_FILE_NAME, e);
+            Error::CodeGeneration(message)
+        })?;
+
+        // Tell Cargo where the manifest is
+        println!(
+            "cargo:rustc-env=ELF_MAGIC_MANIFEST_PATH={}",
+            output_path.display()
+        );
+
+        Ok(())
+    }
+}
+
+impl ProgramEntry {
+    fn new(program: &SolanaProgram, status: ProgramStatus) -> Self {
+        Self {
+            target_name: program.qualified_name(),
+            package_name: program.package_name.clone(),
+            manifest_path: program.manifest_path.clone(),
+            constant_name: program.constant_name.clone(),
+            env_var: program.env_var_name(),
+            status,
+            tags: program.tags.clone(),
+            reason: None,
+            decision: (status != ProgramStatus::Skipped).then(|| program.decision.clone()),
+            so_path: None,
+            size: None,
+            sha256: None,
+            error: None,
+        }
+    }
+}
+
+/// Find the build outcome of an included program
+fn included_entry(
+    program: &SolanaProgram,
+    build_result: &ProgramBuildResult,
+) -> Result<ProgramEntry, Error> {
+    let same_program = |other: &SolanaProgram| {
+        other.manifest_path == program.manifest_path
+            && other.build_target == program.build_target
+            && other.workspace_alias == program.workspace_alias
+    };
+
+    if let Some((_, so_path)) = build_result
+        .successful
+        .iter()
+        .find(|(p, _)| same_program(p))
+    {
+        let bytes = fs::read(so_path).map_err(|e| {
+            let message = format!("Failed to read {}: {}", so_path.display(), e);
+            Error::CodeGeneration(message)
+        })?;
+
+        let mut entry = ProgramEntry::new(program, ProgramStatus::Built);
+        entry.so_path = Some(so_path.clone());
+        entry.size = Some(bytes.len() as u64);
+        entry.sha256 = Some(sha256_hex(&bytes));
+        return Ok(entry);
+    }
+
+    let mut entry = ProgramEntry::new(program, ProgramStatus::Failed);
+    entry.error = build_result
+        .failed
+        .iter()
+        .find(|(p, _)| same_program(p))
+        .map(|(_, error)| error.to_string());
+    Ok(entry)
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::{BuildTarget, FilterRule, PatternOrigin, SkipReason};
+    use tempfile::TempDir;
+
+    fn sample_program(target_name: &str) -> SolanaProgram {
+        SolanaProgram {
+            package_name: target_name.replace('_', "-"),
+            target_name: target_name.to_string(),
+            build_target: BuildTarget::Lib(target_name.to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from(format!("/repo/{}/Cargo.toml", target_name)),
+            constant_name: format!("{}_ELF", target_name.to_uppercase()),
+        }
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_manifest_lists_built_failed_and_excluded_programs() {
+        let temp_dir = TempDir::new().unwrap();
+        let so_path = temp_dir.path().join("vault.so");
+        fs::write(&so_path, b"\x7fELF").unwrap();
+
+        let vault = sample_program("vault");
+        let escrow = sample_program("escrow");
+        let test_program = sample_program("test_program");
+
+        let discovered = DiscoveredPrograms {
+            workspace_path: "./Cargo.toml".to_string(),
+            workspace_alias: None,
+            included: vec![escrow.clone(), vault.clone()],
+            excluded: vec![test_program],
+            skipped: vec![],
+            pattern_usage: vec![],
+        };
+
+        let build_result = ProgramBuildResult {
+            successful: vec![(vault, so_path.clone())],
+            failed: vec![(
+                escrow,
+                Error::ProgramBuild {
+                    program: "escrow".to_string(),
+                    error: "compilation failed".to_string(),
+                },
+            )],
+        };
+
+        let manifest = BuildManifest::new("permissive", &[discovered], &build_result).unwrap();
+        let programs = &manifest.workspaces[0].programs;
+
+        assert_eq!(manifest.mode, "permissive");
+        assert_eq!(programs.len(), 3);
+
+        let escrow = &programs[0];
+        assert_eq!(escrow.status, ProgramStatus::Failed);
+        assert_eq!(
+            escrow.error.as_deref(),
+            Some("Failed to build program escrow: compilation failed")
+        );
+        assert!(escrow.sha256.is_none());
+
+        let vault = &programs[1];
+        assert_eq!(vault.status, ProgramStatus::Built);
+        assert_eq!(vault.constant_name, "VAULT_ELF");
+        assert_eq!(vault.so_path.as_ref(), Some(&so_path));
+        assert_eq!(vault.size, Some(4));
+        assert_eq!(vault.sha256.as_ref().map(|h| h.len()), Some(64));
+
+        let test_program = &programs[2];
+        assert_eq!(test_program.status, ProgramStatus::Excluded);
+        assert!(test_program.so_path.is_none());
+    }
+
+    #[test]
+    fn test_manifest_json_shape() {
+        let mut denied = sample_program("skipped");
+        denied.decision = Decision::by_pattern(
+            FilterRule::Denied,
+            "target:skip*".to_string(),
+            PatternOrigin::GlobalDeny,
+        );
+        let discovered = DiscoveredPrograms {
+            workspace_path: "./Cargo.toml".to_string(),
+            workspace_alias: None,
+            included: vec![],
+            excluded: vec![denied],
+            skipped: vec![(sample_program("wasm_bindings"), SkipReason::NotAProgram)],
+            pattern_usage: vec![],
+        };
+
+        let manifest = BuildManifest::new("magic", &[discovered], &ProgramBuildResult::new())
+            .unwrap()
+            .to_json()
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+
+        assert_eq!(json["mode"], "magic");
+        assert_eq!(json["elf_magic_version"], env!("CARGO_PKG_VERSION"));
+        let program = &json["workspaces"][0]["programs"][0];
+        assert_eq!(program["target_name"], "skipped");
+        assert_eq!(program["status"], "excluded");
+        assert_eq!(program["env_var"], "SKIPPED_ELF_PATH");
+        assert!(program.get("sha256").is_none());
+        assert_eq!(
+            program["decision"],
+            serde_json::json!({
+                "rule": "denied",
+                "pattern": "target:skip*",
+                "origin": "global_deny"
+            })
+        );
+
+        let program = &json["workspaces"][0]["programs"][1];
+        assert_eq!(program["target_name"], "wasm_bindings");
+        assert_eq!(program["status"], "skipped");
+        assert_eq!(program["reason"], "not a Solana program");
+        assert!(program.get("decision").is_none());
+    }
+}
@@ -1,13 +1,126 @@
-use crate::error::Error;
-use std::{fmt, path::PathBuf};
+use crate::{config::BuildSettings, error::Error};
+use serde::Serialize;
+use std::{collections::HashMap, fmt, path::PathBuf};
 
 /// A confirmed Solana program (has crate-type = ["cdylib"])
+///
+/// Identified by its manifest path plus cargo target, since one package can
+/// declare several cdylib targets.
 #[derive(Clone)]
 pub struct SolanaProgram {
     pub manifest_path: PathBuf,
     pub package_name: String,
     pub target_name: String,
     pub constant_name: String,
+    pub build_target: BuildTarget,
+    /// Module the program is generated into (namespaced layout only)
+    pub workspace_alias: Option<String>,
+    /// Base58 program ID from the `program_ids` config or the program's `declare_id!`
+    pub program_id: Option<String>,
+    /// How `cargo build-sbf` is run: workspace settings under the program's own
+    pub build: BuildSettings,
+    /// Free-form labels from the program's own `[package.metadata.elf-magic]`
+    pub tags: Vec<String>,
+    /// Why discovery included or excluded the program
+    pub decision: Decision,
+}
+
+/// Record of the filter rule, and the pattern if any, that included or excluded a program
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct Decision {
+    pub rule: FilterRule,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub origin: Option<PatternOrigin>,
+}
+
+/// The mode rule that decided whether a program is included
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterRule {
+    /// Magic mode includes every program
+    #[default]
+    IncludeAll,
+    /// No deny pattern matched
+    NotDenied,
+    /// A deny pattern matched
+    Denied,
+    /// An only pattern matched
+    OnlyMatched,
+    /// No only pattern matched
+    NotInOnly,
+}
+
+impl Decision {
+    /// Decided by `rule` alone, without any pattern matching
+    pub fn by_rule(rule: FilterRule) -> Self {
+        Self {
+            rule,
+            pattern: None,
+            origin: None,
+        }
+    }
+
+    /// Decided by `rule` because `pattern` from the `origin` list matched
+    pub fn by_pattern(rule: FilterRule, pattern: String, origin: PatternOrigin) -> Self {
+        Self {
+            rule,
+            pattern: Some(pattern),
+            origin: Some(origin),
+        }
+    }
+
+    pub fn is_included(&self) -> bool {
+        matches!(
+            self.rule,
+            FilterRule::IncludeAll | FilterRule::NotDenied | FilterRule::OnlyMatched
+        )
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pattern = self.pattern.as_deref().unwrap_or_default();
+        let origin = self.origin.map(|o| o.to_string()).unwrap_or_default();
+        match self.rule {
+            FilterRule::IncludeAll => write!(f, "magic mode includes every program"),
+            FilterRule::NotDenied => write!(f, "no deny pattern matched"),
+            FilterRule::Denied => write!(f, "denied by {} from {}", pattern, origin),
+            FilterRule::OnlyMatched => write!(f, "matched {} from {}", pattern, origin),
+            FilterRule::NotInOnly => write!(f, "no only pattern matched"),
+        }
+    }
+}
+
+/// The cargo target that produces a program's `.so`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum BuildTarget {
+    /// The package's `[lib]` target
+    Lib(String),
+    /// An `[[example]]` target with `crate-type = ["cdylib"]`, built into the workspace target directory
+    Example {
+        name: String,
+        target_directory: PathBuf,
+    },
+}
+
+impl BuildTarget {
+    /// Cargo target name (before any `targets` override)
+    pub fn name(&self) -> &str {
+        match self {
+            BuildTarget::Lib(name) => name,
+            BuildTarget::Example { name, .. } => name,
+        }
+    }
+
+    /// Target selection args passed through `cargo build-sbf` to `cargo build`
+    pub fn cargo_args(&self) -> Vec<String> {
+        match self {
+            BuildTarget::Lib(_) => vec!["--lib".to_string()],
+            BuildTarget::Example { name, .. } => vec!["--example".to_string(), name.clone()],
+        }
+    }
 }
 
 /// Result of building multiple Solana programs
@@ -37,7 +150,47 @@ impl ProgramBuildResult {
 impl SolanaProgram {
     /// Convert target name to environment variable name
     pub fn env_var_name(&self) -> String {
-        format!("{}_ELF_PATH", self.target_name.to_uppercase())
+        format!("{}_ELF_PATH", self.qualified_env_name())
+    }
+
+    /// Runtime environment variable that overrides the embedded ELF bytes
+    pub fn override_env_var_name(&self) -> String {
+        format!("ELF_MAGIC_OVERRIDE_{}", self.qualified_env_name())
+    }
+
+    /// Program name qualified by its workspace module, if any (e.g. `examples::vault`)
+    pub fn qualified_name(&self) -> String {
+        match &self.workspace_alias {
+            Some(alias) => format!("{}::{}", alias, self.target_name),
+            None => self.target_name.clone(),
+        }
+    }
+
+    /// Uppercased target name, prefixed by the workspace alias in the namespaced layout
+    /// so env vars stay unique across modules
+    fn qualified_env_name(&self) -> String {
+        match &self.workspace_alias {
+            Some(alias) => format!("{}_{}", alias, self.target_name).to_uppercase(),
+            None => self.target_name.to_uppercase(),
+        }
+    }
+
+    /// Name of the generated accessor function for this program
+    pub fn accessor_name(&self) -> String {
+        self.constant_name.to_lowercase()
+    }
+
+    /// CamelCase `harness::Program` variant for the qualified name, e.g. `examples::token_vault` -> `ExamplesTokenVault`
+    pub fn variant_name(&self) -> String {
+        self.qualified_name()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                let mut chars = word.chars();
+                let first = chars.next().map(|c| c.to_ascii_uppercase());
+                first.into_iter().chain(chars).collect::<String>()
+            })
+            .collect()
     }
 }
 
@@ -45,25 +198,204 @@ impl fmt::Debug for SolanaProgram {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SolanaProgram")
             .field("target_name", &self.target_name)
+            .field("build_target", &self.build_target)
+            .field("workspace_alias", &self.workspace_alias)
             .field("manifest_path", &self.manifest_path.display())
             .field("env_var_name", &self.env_var_name())
+            .field("override_env_var_name", &self.override_env_var_name())
             .field("constant_name", &self.constant_name)
+            .field("program_id", &self.program_id)
+            .field("build", &self.build)
+            .field("tags", &self.tags)
+            .field("decision", &self.decision)
             .finish()
     }
 }
 
 impl fmt::Display for SolanaProgram {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} ({})", self.target_name, self.manifest_path.display())
+        write!(
+            f,
+            "{} ({})",
+            self.qualified_name(),
+            self.manifest_path.display()
+        )
     }
 }
 
+/// Decode a base58 program ID into its 32 pubkey bytes
+pub fn decode_program_id(program_id: &str) -> Option<[u8; 32]> {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    // Big-endian base256 accumulator, long enough for any 32-byte key
+    let mut bytes = [0u8; 33];
+    for c in program_id.bytes() {
+        let mut carry = ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+
+    // Leading '1's encode leading zero bytes
+    let leading_zeros = program_id.bytes().take_while(|&c| c == b'1').count();
+    let significant = bytes.iter().skip_while(|&&b| b == 0).count();
+    if leading_zeros + significant != 32 || bytes[0] != 0 {
+        return None;
+    }
+
+    bytes[1..].try_into().ok()
+}
+
+/// With `strict_only`, an `only` pattern that matches nothing (e.g. after a rename) fails the build
+pub fn check_unused_only_patterns(discovered_programs: &[DiscoveredPrograms]) -> Result<(), Error> {
+    let unused: Vec<String> = find_unused_patterns(discovered_programs)
+        .into_iter()
+        .filter(|unused| unused.origin.is_only())
+        .map(|unused| match unused.workspace_path {
+            Some(path) => format!("{} (in {})", unused.pattern, path),
+            None => unused.pattern,
+        })
+        .collect();
+
+    if unused.is_empty() {
+        return Ok(());
+    }
+
+    Err(Error::Config(format!(
+        "`only` patterns matched no programs: {}",
+        unused.join(", ")
+    )))
+}
+
 /// Details about what happened in a single workspace
 #[derive(Debug, Clone)]
 pub struct DiscoveredPrograms {
     pub workspace_path: String,
+    /// Module name in the namespaced layout
+    pub workspace_alias: Option<String>,
     pub included: Vec<SolanaProgram>,
     pub excluded: Vec<SolanaProgram>,
+    /// cdylib targets left out before filtering, and why
+    pub skipped: Vec<(SolanaProgram, SkipReason)>,
+    /// How many programs each filter pattern matched in this workspace
+    pub pattern_usage: Vec<PatternUsage>,
+}
+
+/// Which config list a filter pattern came from
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternOrigin {
+    GlobalDeny,
+    Deny,
+    GlobalOnly,
+    Only,
+}
+
+impl PatternOrigin {
+    /// Global patterns apply to every workspace, so they're only unused if no workspace matched
+    pub fn is_global(self) -> bool {
+        matches!(self, PatternOrigin::GlobalDeny | PatternOrigin::GlobalOnly)
+    }
+
+    /// Whether the pattern narrows what's included, rather than excluding
+    pub fn is_only(self) -> bool {
+        matches!(self, PatternOrigin::GlobalOnly | PatternOrigin::Only)
+    }
+}
+
+impl fmt::Display for PatternOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternOrigin::GlobalDeny => write!(f, "global_deny"),
+            PatternOrigin::Deny => write!(f, "deny"),
+            PatternOrigin::GlobalOnly => write!(f, "global_only"),
+            PatternOrigin::Only => write!(f, "only"),
+        }
+    }
+}
+
+/// How many programs a filter pattern matched in one workspace
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PatternUsage {
+    pub pattern: String,
+    pub origin: PatternOrigin,
+    pub matches: usize,
+}
+
+/// A filter pattern that matched no program
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct UnusedPattern {
+    pub pattern: String,
+    pub origin: PatternOrigin,
+    /// The workspace it was configured for, or `None` for global patterns
+    pub workspace_path: Option<String>,
+}
+
+/// Patterns that matched nothing: workspace patterns per workspace, global ones across all
+pub fn find_unused_patterns(discovered: &[DiscoveredPrograms]) -> Vec<UnusedPattern> {
+    let mut unused = Vec::new();
+    let mut global: Vec<(&PatternUsage, usize)> = Vec::new();
+
+    for workspace in discovered {
+        for usage in &workspace.pattern_usage {
+            if usage.origin.is_global() {
+                let seen = global.iter_mut().find(|(first, _)| {
+                    first.pattern == usage.pattern && first.origin == usage.origin
+                });
+                match seen {
+                    Some((_, matches)) => *matches += usage.matches,
+                    None => global.push((usage, usage.matches)),
+                }
+            } else if usage.matches == 0 {
+                unused.push(UnusedPattern {
+                    pattern: usage.pattern.clone(),
+                    origin: usage.origin,
+                    workspace_path: Some(workspace.workspace_path.clone()),
+                });
+            }
+        }
+    }
+
+    unused.extend(
+        global
+            .into_iter()
+            .filter(|(_, matches)| *matches == 0)
+            .map(|(usage, _)| UnusedPattern {
+                pattern: usage.pattern.clone(),
+                origin: usage.origin,
+                workspace_path: None,
+            }),
+    );
+    unused
+}
+
+/// Why a cdylib target was left out before any filtering
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SkipReason {
+    /// No Solana program dependency, or `program = false`
+    NotAProgram,
+    /// `skip = true` in the program's own `[package.metadata.elf-magic]`
+    SkippedByPackage,
+    /// The ELF crate running elf-magic
+    ElvesCrate,
+    /// Depends on the ELF crate, so building it would run elf-magic from inside itself
+    DependsOnElvesCrate,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::NotAProgram => write!(f, "not a Solana program"),
+            SkipReason::SkippedByPackage => write!(f, "skip = true in its Cargo.toml"),
+            SkipReason::ElvesCrate => write!(f, "the ELF crate itself"),
+            SkipReason::DependsOnElvesCrate => write!(f, "depends on the ELF crate"),
+        }
+    }
 }
 
 /// Result of the entire generation process with rich reporting
@@ -86,14 +418,26 @@ impl fmt::Display for BuildResults {
         writeln!(f)?;
 
         for workspace in &self.discovered_programs {
-            writeln!(f, "Workspace: {}", workspace.workspace_path)?;
+            match &workspace.workspace_alias {
+                Some(alias) => {
+                    writeln!(f, "Workspace: {} (mod {})", workspace.workspace_path, alias)?
+                }
+                None => writeln!(f, "Workspace: {}", workspace.workspace_path)?,
+            }
 
             for program in &workspace.included {
-                writeln!(f, "  + {}", program.target_name)?;
+                match program.decision.rule {
+                    FilterRule::IncludeAll => writeln!(f, "  + {}", program.target_name)?,
+                    _ => writeln!(f, "  + {} ({})", program.target_name, program.decision)?,
+                }
             }
 
             for excluded in &workspace.excluded {
-                writeln!(f, "  - {} (denied by pattern)", excluded.target_name)?;
+                writeln!(f, "  - {} ({})", excluded.target_name, excluded.decision)?;
+            }
+
+            for (skipped, reason) in &workspace.skipped {
+                writeln!(f, "  ~ {} (skipped: {})", skipped.target_name, reason)?;
             }
 
             if workspace.included.is_empty() && workspace.excluded.is_empty() {
@@ -103,6 +447,26 @@ impl fmt::Display for BuildResults {
             writeln!(f)?;
         }
 
+        let unused = find_unused_patterns(&self.discovered_programs);
+        if !unused.is_empty() {
+            writeln!(f, "Unused patterns:")?;
+            for pattern in &unused {
+                match &pattern.workspace_path {
+                    Some(path) => writeln!(
+                        f,
+                        "  ? {} ({} in {} matched nothing)",
+                        pattern.pattern, pattern.origin, path
+                    )?,
+                    None => writeln!(
+                        f,
+                        "  ? {} ({} matched nothing)",
+                        pattern.pattern, pattern.origin
+                    )?,
+                }
+            }
+            writeln!(f)?;
+        }
+
         let total_programs: usize = self
             .discovered_programs
             .iter()
@@ -141,26 +505,102 @@ impl BuildResults {
     }
 }
 
-/// Deduplicate programs by manifest_path to handle cases where multiple workspaces
-/// discover the same program (e.g., shared dependencies)
+/// Deduplicate programs by (manifest_path, cargo target, workspace alias) to handle cases
+/// where multiple workspaces discover the same program (e.g., shared dependencies)
+///
+/// In the namespaced layout each workspace module keeps its own copy.
 pub fn deduplicate_programs(programs: Vec<SolanaProgram>) -> Vec<SolanaProgram> {
-    use std::collections::HashMap;
-
-    let mut seen: HashMap<PathBuf, SolanaProgram> = HashMap::new();
+    let mut seen: HashMap<(PathBuf, String, Option<String>), SolanaProgram> = HashMap::new();
 
     for program in programs {
-        // Use manifest_path as the key, keeping the first occurrence
-        seen.entry(program.manifest_path.clone()).or_insert(program);
+        // Use (manifest_path, cargo target, alias) as the key, keeping the first occurrence
+        let key = (
+            program.manifest_path.clone(),
+            program.build_target.name().to_string(),
+            program.workspace_alias.clone(),
+        );
+        seen.entry(key).or_insert(program);
     }
 
     let mut deduplicated: Vec<SolanaProgram> = seen.into_values().collect();
 
     // Sort by target_name to ensure consistent alphabetical ordering
-    deduplicated.sort_by(|a, b| a.target_name.cmp(&b.target_name));
+    deduplicated.sort_by(|a, b| {
+        (&a.target_name, &a.workspace_alias).cmp(&(&b.target_name, &b.workspace_alias))
+    });
 
     deduplicated
 }
 
+/// Items the generated code defines or imports next to the program constants and accessors
+const GENERATED_ITEM_NAMES: &[&str] = &[
+    "Elf",
+    "AlignedBytes",
+    "load_elf",
+    "Status",
+    "BUILD_STATUS",
+    "failed_programs",
+    "elves",
+    "harness",
+];
+
+/// Detect programs that would generate the same identifier, or one the generated code already uses
+///
+/// Runs after deduplication and override resolution, so any remaining clash is between
+/// genuinely different programs (e.g. two `vault` programs from different workspaces).
+/// Constants, accessors and targets only clash within the same workspace module; the
+/// `harness::Program` variants and env vars share one namespace.
+pub fn detect_name_collisions(programs: &[SolanaProgram]) -> Result<(), Error> {
+    let mut seen: HashMap<(&str, Option<&str>, String), &SolanaProgram> = HashMap::new();
+    let mut collisions = Vec::new();
+
+    for program in programs {
+        let module = program.workspace_alias.as_deref();
+        let names = [
+            ("constant", module, program.constant_name.clone()),
+            ("accessor", module, program.accessor_name()),
+            ("target", module, program.target_name.clone()),
+            ("harness variant", None, program.variant_name()),
+            ("env var", None, program.env_var_name()),
+        ];
+
+        for (kind, _, name) in &names[..2] {
+            if GENERATED_ITEM_NAMES.contains(&name.as_str()) {
+                collisions.push(format!(
+                    "{} `{}` for {} clashes with an item elf-magic generates",
+                    kind,
+                    name,
+                    program.manifest_path.display()
+                ));
+            }
+        }
+
+        for (kind, module, name) in names {
+            match seen.get(&(kind, module, name.clone())) {
+                Some(first) => collisions.push(format!(
+                    "{} `{}` is produced by both {} and {}",
+                    kind,
+                    name,
+                    first.manifest_path.display(),
+                    program.manifest_path.display()
+                )),
+                None => {
+                    seen.insert((kind, module, name), program);
+                }
+            }
+        }
+    }
+
+    if collisions.is_empty() {
+        return Ok(());
+    }
+
+    Err(Error::NameCollision(format!(
+        "{}\nAdd a `constants` or `targets` override for one of these manifests in [package.metadata.elf-magic]",
+        collisions.join("\n")
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,6 +610,12 @@ mod tests {
         SolanaProgram {
             package_name: "my-package".to_string(),
             target_name: "my_target".to_string(),
+            build_target: BuildTarget::Lib("my_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         }
@@ -181,11 +627,71 @@ mod tests {
         assert_eq!(program.env_var_name(), "MY_TARGET_ELF_PATH");
     }
 
+    #[test]
+    fn test_decode_program_id() {
+        assert_eq!(
+            decode_program_id("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+            Some([
+                6, 221, 246, 225, 215, 101, 161, 147, 217, 203, 225, 70, 206, 235, 121, 172, 28,
+                180, 133, 237, 95, 91, 55, 145, 58, 140, 245, 133, 126, 255, 0, 169
+            ])
+        );
+        assert_eq!(
+            decode_program_id("11111111111111111111111111111111"),
+            Some([0; 32])
+        );
+
+        // Invalid alphabet, too short, too long
+        assert_eq!(
+            decode_program_id("Tokenkeg0feZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+            None
+        );
+        assert_eq!(decode_program_id("Tokenkeg"), None);
+        assert_eq!(
+            decode_program_id("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DATokenkeg"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_override_env_var_name() {
+        let program = sample_program();
+        assert_eq!(
+            program.override_env_var_name(),
+            "ELF_MAGIC_OVERRIDE_MY_TARGET"
+        );
+    }
+
+    #[test]
+    fn test_accessor_name() {
+        let program = sample_program();
+        assert_eq!(program.accessor_name(), "my_target_elf");
+    }
+
+    #[test]
+    fn test_env_var_names_with_workspace_alias() {
+        let mut program = sample_program();
+        program.workspace_alias = Some("examples".to_string());
+
+        assert_eq!(program.env_var_name(), "EXAMPLES_MY_TARGET_ELF_PATH");
+        assert_eq!(
+            program.override_env_var_name(),
+            "ELF_MAGIC_OVERRIDE_EXAMPLES_MY_TARGET"
+        );
+        assert_eq!(program.qualified_name(), "examples::my_target");
+    }
+
     #[test]
     fn test_env_var_name_with_hyphens() {
         let program = SolanaProgram {
             package_name: "my-package".to_string(),
             target_name: "my_target_program".to_string(),
+            build_target: BuildTarget::Lib("my_target_program".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/path/to/Cargo.toml"),
             constant_name: "MY_TARGET_PROGRAM_ELF".to_string(),
         };
@@ -198,6 +704,12 @@ mod tests {
         let apl_token_duplicate1 = SolanaProgram {
             package_name: "apl-token".to_string(),
             target_name: "apl_token".to_string(),
+            build_target: BuildTarget::Lib("apl_token".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/repo/token/Cargo.toml"),
             constant_name: "APL_TOKEN_ELF".to_string(),
         };
@@ -205,6 +717,12 @@ mod tests {
         let apl_token_duplicate2 = SolanaProgram {
             package_name: "apl-token".to_string(),
             target_name: "apl_token".to_string(),
+            build_target: BuildTarget::Lib("apl_token".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/repo/token/Cargo.toml"), // Same path!
             constant_name: "APL_TOKEN_ELF".to_string(),
         };
@@ -212,6 +730,12 @@ mod tests {
         let escrow_program = SolanaProgram {
             package_name: "escrow_program".to_string(),
             target_name: "escrow_program".to_string(),
+            build_target: BuildTarget::Lib("escrow_program".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/repo/examples/escrow/program/Cargo.toml"),
             constant_name: "ESCROW_PROGRAM_ELF".to_string(),
         };
@@ -254,6 +778,12 @@ mod tests {
         let program1 = SolanaProgram {
             package_name: "package1".to_string(),
             target_name: "target1".to_string(),
+            build_target: BuildTarget::Lib("target1".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/workspace/program1/Cargo.toml"),
             constant_name: "TARGET1_ELF".to_string(),
         };
@@ -261,6 +791,12 @@ mod tests {
         let program2 = SolanaProgram {
             package_name: "package2".to_string(),
             target_name: "target2".to_string(),
+            build_target: BuildTarget::Lib("target2".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/workspace/program2/Cargo.toml"),
             constant_name: "TARGET2_ELF".to_string(),
         };
@@ -273,11 +809,300 @@ mod tests {
         assert_eq!(deduplicated.len(), 2, "Should preserve all unique programs");
     }
 
+    #[test]
+    fn test_deduplicate_programs_keeps_multiple_targets_per_manifest() {
+        let lib_program = SolanaProgram {
+            package_name: "multi".to_string(),
+            target_name: "multi".to_string(),
+            build_target: BuildTarget::Lib("multi".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/repo/multi/Cargo.toml"),
+            constant_name: "MULTI_ELF".to_string(),
+        };
+
+        let example_program = SolanaProgram {
+            package_name: "multi".to_string(),
+            target_name: "multi_example".to_string(),
+            build_target: BuildTarget::Example {
+                name: "multi_example".to_string(),
+                target_directory: PathBuf::from("/repo/target"),
+            },
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/repo/multi/Cargo.toml"), // Same manifest!
+            constant_name: "MULTI_EXAMPLE_ELF".to_string(),
+        };
+
+        let deduplicated =
+            deduplicate_programs(vec![lib_program.clone(), example_program, lib_program]);
+
+        assert_eq!(deduplicated.len(), 2, "Should keep one program per target");
+        assert_eq!(deduplicated[0].target_name, "multi");
+        assert_eq!(deduplicated[1].target_name, "multi_example");
+    }
+
+    #[test]
+    fn test_build_target_cargo_args() {
+        assert_eq!(
+            BuildTarget::Lib("vault".to_string()).cargo_args(),
+            vec!["--lib"]
+        );
+        assert_eq!(
+            BuildTarget::Example {
+                name: "vault_example".to_string(),
+                target_directory: PathBuf::from("/repo/target"),
+            }
+            .cargo_args(),
+            vec!["--example", "vault_example"]
+        );
+    }
+
+    #[test]
+    fn test_detect_name_collisions_none() {
+        let programs = vec![
+            SolanaProgram {
+                package_name: "vault".to_string(),
+                target_name: "vault".to_string(),
+                build_target: BuildTarget::Lib("vault".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/vault/Cargo.toml"),
+                constant_name: "VAULT_ELF".to_string(),
+            },
+            SolanaProgram {
+                package_name: "escrow".to_string(),
+                target_name: "escrow".to_string(),
+                build_target: BuildTarget::Lib("escrow".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/escrow/Cargo.toml"),
+                constant_name: "ESCROW_ELF".to_string(),
+            },
+        ];
+
+        assert!(detect_name_collisions(&programs).is_ok());
+    }
+
+    #[test]
+    fn test_detect_name_collisions_same_target_different_workspaces() {
+        let programs = vec![
+            SolanaProgram {
+                package_name: "vault".to_string(),
+                target_name: "vault".to_string(),
+                build_target: BuildTarget::Lib("vault".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/programs/vault/Cargo.toml"),
+                constant_name: "VAULT_ELF".to_string(),
+            },
+            SolanaProgram {
+                package_name: "vault".to_string(),
+                target_name: "vault".to_string(),
+                build_target: BuildTarget::Lib("vault".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/examples/vault/Cargo.toml"),
+                constant_name: "VAULT_ELF".to_string(),
+            },
+        ];
+
+        let error = detect_name_collisions(&programs).unwrap_err().to_string();
+        assert!(error.contains("constant `VAULT_ELF` is produced by both /repo/programs/vault/Cargo.toml and /repo/examples/vault/Cargo.toml"));
+        assert!(error.contains("target `vault`"));
+        assert!(error.contains("env var `VAULT_ELF_PATH`"));
+        assert!(error.contains("Add a `constants` or `targets` override"));
+    }
+
+    #[test]
+    fn test_detect_name_collisions_after_constant_override() {
+        // Constant override resolves the constant clash, but the target (and env var) still collide
+        let programs = vec![
+            SolanaProgram {
+                package_name: "vault".to_string(),
+                target_name: "vault".to_string(),
+                build_target: BuildTarget::Lib("vault".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/programs/vault/Cargo.toml"),
+                constant_name: "VAULT_ELF".to_string(),
+            },
+            SolanaProgram {
+                package_name: "vault".to_string(),
+                target_name: "vault".to_string(),
+                build_target: BuildTarget::Lib("vault".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/examples/vault/Cargo.toml"),
+                constant_name: "EXAMPLE_VAULT_ELF".to_string(),
+            },
+        ];
+
+        let error = detect_name_collisions(&programs).unwrap_err().to_string();
+        assert!(!error.contains("constant `"));
+        assert!(error.contains("target `vault`"));
+    }
+
+    #[test]
+    fn test_detect_name_collisions_allows_same_names_in_different_modules() {
+        let programs = vec![
+            SolanaProgram {
+                package_name: "vault".to_string(),
+                target_name: "vault".to_string(),
+                build_target: BuildTarget::Lib("vault".to_string()),
+                workspace_alias: Some("core".to_string()),
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/programs/vault/Cargo.toml"),
+                constant_name: "VAULT_ELF".to_string(),
+            },
+            SolanaProgram {
+                package_name: "vault".to_string(),
+                target_name: "vault".to_string(),
+                build_target: BuildTarget::Lib("vault".to_string()),
+                workspace_alias: Some("examples".to_string()),
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/examples/vault/Cargo.toml"),
+                constant_name: "VAULT_ELF".to_string(),
+            },
+        ];
+
+        assert!(detect_name_collisions(&programs).is_ok());
+    }
+
+    #[test]
+    fn test_detect_name_collisions_env_var_case() {
+        // Distinct target names can still map to the same env var
+        let programs = vec![
+            SolanaProgram {
+                package_name: "vault".to_string(),
+                target_name: "vault".to_string(),
+                build_target: BuildTarget::Lib("vault".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/a/Cargo.toml"),
+                constant_name: "VAULT_ELF".to_string(),
+            },
+            SolanaProgram {
+                package_name: "Vault".to_string(),
+                target_name: "Vault".to_string(),
+                build_target: BuildTarget::Lib("Vault".to_string()),
+                workspace_alias: None,
+                program_id: None,
+                build: Default::default(),
+                tags: vec![],
+                decision: Default::default(),
+                manifest_path: PathBuf::from("/repo/b/Cargo.toml"),
+                constant_name: "OTHER_VAULT_ELF".to_string(),
+            },
+        ];
+
+        let error = detect_name_collisions(&programs).unwrap_err().to_string();
+        assert!(!error.contains("target `"));
+        assert!(error.contains("env var `VAULT_ELF_PATH`"));
+    }
+
+    #[test]
+    fn test_detect_name_collisions_in_generated_identifiers() {
+        let program = |target_name: &str, constant_name: &str| SolanaProgram {
+            package_name: target_name.to_string(),
+            target_name: target_name.to_string(),
+            build_target: BuildTarget::Lib(target_name.to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from(format!("/repo/{}/Cargo.toml", target_name)),
+            constant_name: constant_name.to_string(),
+        };
+
+        // Distinct targets and constants, same `harness::Program` variant
+        let programs = [
+            program("token_2022", "TOKEN_2022_ELF"),
+            program("token2022", "TOKEN2022_ELF"),
+        ];
+        let error = detect_name_collisions(&programs).unwrap_err().to_string();
+        assert!(error.contains("harness variant `Token2022` is produced by both /repo/token_2022/Cargo.toml and /repo/token2022/Cargo.toml"));
+
+        // Constants differing only in case share an accessor
+        let programs = [
+            program("vault", "VAULT_ELF"),
+            program("escrow", "Vault_ELF"),
+        ];
+        let error = detect_name_collisions(&programs).unwrap_err().to_string();
+        assert!(error.contains("accessor `vault_elf`"));
+
+        // Overrides can't reuse the names of generated items
+        let error = detect_name_collisions(&[program("vault", "BUILD_STATUS")])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains(
+            "constant `BUILD_STATUS` for /repo/vault/Cargo.toml clashes with an item elf-magic generates"
+        ));
+        let error = detect_name_collisions(&[program("vault", "ELVES")])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("accessor `elves`"));
+    }
+
+    #[test]
+    fn test_variant_name() {
+        let mut program = sample_program();
+        program.target_name = "token_program".to_string();
+        assert_eq!(program.variant_name(), "TokenProgram");
+
+        program.target_name = "my-complex-target-name".to_string();
+        assert_eq!(program.variant_name(), "MyComplexTargetName");
+
+        program.target_name = "vault".to_string();
+        program.workspace_alias = Some("examples".to_string());
+        assert_eq!(program.variant_name(), "ExamplesVault");
+    }
+
     #[test]
     fn test_generation_result_display_magic_mode() {
         let program1 = SolanaProgram {
             package_name: "pkg1".to_string(),
             target_name: "target1".to_string(),
+            build_target: BuildTarget::Lib("target1".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/workspace/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
@@ -285,14 +1110,23 @@ mod tests {
         let program2 = SolanaProgram {
             package_name: "pkg2".to_string(),
             target_name: "target2".to_string(),
+            build_target: BuildTarget::Lib("target2".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/workspace/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
 
         let discovered = DiscoveredPrograms {
             workspace_path: "./Cargo.toml".to_string(),
+            workspace_alias: None,
             included: vec![program1, program2],
             excluded: vec![],
+            skipped: vec![],
+            pattern_usage: vec![],
         };
 
         let result = BuildResults::new("magic".to_string(), vec![discovered]);
@@ -310,6 +1144,12 @@ mod tests {
         let included_program = SolanaProgram {
             package_name: "included".to_string(),
             target_name: "good_target".to_string(),
+            build_target: BuildTarget::Lib("good_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Decision::by_rule(FilterRule::NotDenied),
             manifest_path: PathBuf::from("/workspace/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
@@ -317,31 +1157,78 @@ mod tests {
         let excluded_program = SolanaProgram {
             package_name: "excluded".to_string(),
             target_name: "bad_target".to_string(),
+            build_target: BuildTarget::Lib("bad_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Decision::by_pattern(
+                FilterRule::Denied,
+                "package:excluded".to_string(),
+                PatternOrigin::GlobalDeny,
+            ),
             manifest_path: PathBuf::from("/workspace/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
 
+        let only_program = SolanaProgram {
+            target_name: "vault".to_string(),
+            decision: Decision::by_pattern(
+                FilterRule::OnlyMatched,
+                "target:vault*".to_string(),
+                PatternOrigin::Only,
+            ),
+            ..included_program.clone()
+        };
+        let unmatched_program = SolanaProgram {
+            target_name: "escrow".to_string(),
+            decision: Decision::by_rule(FilterRule::NotInOnly),
+            ..included_program.clone()
+        };
+
+        let skipped_program = SolanaProgram {
+            package_name: "bindings".to_string(),
+            target_name: "wasm_bindings".to_string(),
+            build_target: BuildTarget::Lib("wasm_bindings".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/workspace/Cargo.toml"),
+            constant_name: "WASM_BINDINGS_ELF".to_string(),
+        };
+
         let discovered = DiscoveredPrograms {
             workspace_path: "./Cargo.toml".to_string(),
-            included: vec![included_program],
-            excluded: vec![excluded_program],
+            workspace_alias: None,
+            included: vec![included_program, only_program],
+            excluded: vec![excluded_program, unmatched_program],
+            skipped: vec![(skipped_program, SkipReason::NotAProgram)],
+            pattern_usage: vec![],
         };
 
         let result = BuildResults::new("permissive".to_string(), vec![discovered]);
         let display = format!("{}", result);
 
         assert!(display.contains("Mode: permissive"));
-        assert!(display.contains("  + good_target"));
-        assert!(display.contains("  - bad_target (denied by pattern)"));
-        assert!(display.contains("Generated lib.rs with 1 Solana program"));
+        assert!(display.contains("  + good_target (no deny pattern matched)"));
+        assert!(display.contains("  - bad_target (denied by package:excluded from global_deny)"));
+        assert!(display.contains("  + vault (matched target:vault* from only)"));
+        assert!(display.contains("  - escrow (no only pattern matched)"));
+        assert!(display.contains("  ~ wasm_bindings (skipped: not a Solana program)"));
+        assert!(display.contains("Generated lib.rs with 2 Solana programs"));
     }
 
     #[test]
     fn test_generation_result_display_no_programs() {
         let discovered = DiscoveredPrograms {
             workspace_path: "./empty/Cargo.toml".to_string(),
+            workspace_alias: None,
             included: vec![],
             excluded: vec![],
+            skipped: vec![],
+            pattern_usage: vec![],
         };
 
         let result = BuildResults::new("magic".to_string(), vec![discovered]);
@@ -351,11 +1238,119 @@ mod tests {
         assert!(display.contains("⚠️  No Solana programs found - generated empty lib.rs"));
     }
 
+    fn usage(pattern: &str, origin: PatternOrigin, matches: usize) -> PatternUsage {
+        PatternUsage {
+            pattern: pattern.to_string(),
+            origin,
+            matches,
+        }
+    }
+
+    fn workspace_with_usage(path: &str, pattern_usage: Vec<PatternUsage>) -> DiscoveredPrograms {
+        DiscoveredPrograms {
+            workspace_path: path.to_string(),
+            workspace_alias: None,
+            included: vec![],
+            excluded: vec![],
+            skipped: vec![],
+            pattern_usage,
+        }
+    }
+
+    #[test]
+    fn test_find_unused_patterns() {
+        let discovered = vec![
+            workspace_with_usage(
+                "./Cargo.toml",
+                vec![
+                    usage("package:apl-*", PatternOrigin::GlobalDeny, 0),
+                    usage("target:bench*", PatternOrigin::GlobalDeny, 0),
+                    usage("target:test*", PatternOrigin::Deny, 0),
+                ],
+            ),
+            workspace_with_usage(
+                "examples/Cargo.toml",
+                vec![
+                    usage("package:apl-*", PatternOrigin::GlobalDeny, 2),
+                    usage("target:bench*", PatternOrigin::GlobalDeny, 0),
+                ],
+            ),
+        ];
+
+        // Global patterns only count as unused when no workspace matched them
+        assert_eq!(
+            find_unused_patterns(&discovered),
+            vec![
+                UnusedPattern {
+                    pattern: "target:test*".to_string(),
+                    origin: PatternOrigin::Deny,
+                    workspace_path: Some("./Cargo.toml".to_string()),
+                },
+                UnusedPattern {
+                    pattern: "target:bench*".to_string(),
+                    origin: PatternOrigin::GlobalDeny,
+                    workspace_path: None,
+                },
+            ]
+        );
+
+        let display = format!(
+            "{}",
+            BuildResults::new("permissive".to_string(), discovered)
+        );
+        assert!(display.contains("Unused patterns:"));
+        assert!(display.contains("  ? target:test* (deny in ./Cargo.toml matched nothing)"));
+        assert!(display.contains("  ? target:bench* (global_deny matched nothing)"));
+        assert!(!display.contains("package:apl-*"));
+    }
+
+    #[test]
+    fn test_check_unused_only_patterns() {
+        let discovered = vec![workspace_with_usage(
+            "./Cargo.toml",
+            vec![
+                usage("target:vault", PatternOrigin::Only, 1),
+                usage("target:old_name", PatternOrigin::Only, 0),
+            ],
+        )];
+        let error = check_unused_only_patterns(&discovered)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("target:old_name (in ./Cargo.toml)"));
+        assert!(!error.contains("target:vault"));
+
+        // A global_only pattern counts once it matches in any workspace
+        let discovered = vec![
+            workspace_with_usage(
+                "./Cargo.toml",
+                vec![usage("tag:core", PatternOrigin::GlobalOnly, 0)],
+            ),
+            workspace_with_usage(
+                "examples/Cargo.toml",
+                vec![usage("tag:core", PatternOrigin::GlobalOnly, 2)],
+            ),
+        ];
+        assert!(check_unused_only_patterns(&discovered).is_ok());
+
+        // Unused deny patterns are only ever reported
+        let discovered = vec![workspace_with_usage(
+            "./Cargo.toml",
+            vec![usage("target:test*", PatternOrigin::Deny, 0)],
+        )];
+        assert!(check_unused_only_patterns(&discovered).is_ok());
+    }
+
     #[test]
     fn test_generation_result_programs_method() {
         let program1 = SolanaProgram {
             package_name: "pkg1".to_string(),
             target_name: "target1".to_string(),
+            build_target: BuildTarget::Lib("target1".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/workspace/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
@@ -363,20 +1358,32 @@ mod tests {
         let program2 = SolanaProgram {
             package_name: "pkg2".to_string(),
             target_name: "target2".to_string(),
+            build_target: BuildTarget::Lib("target2".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             manifest_path: PathBuf::from("/workspace2/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
 
         let discovered1 = DiscoveredPrograms {
             workspace_path: "./Cargo.toml".to_string(),
+            workspace_alias: None,
             included: vec![program1],
             excluded: vec![],
+            skipped: vec![],
+            pattern_usage: vec![],
         };
 
         let discovered2 = DiscoveredPrograms {
             workspace_path: "./workspace2/Cargo.toml".to_string(),
+            workspace_alias: None,
             included: vec![program2],
             excluded: vec![],
+            skipped: vec![],
+            pattern_usage: vec![],
         };
 
         let result = BuildResults::new("permissive".to_string(), vec![discovered1, discovered2]);
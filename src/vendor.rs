@@ -0,0 +1,354 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    builder::write_if_changed, codegen::split_header, config::VendorConfig, error::Error,
+    manifest::sha256_hex, programs::ProgramBuildResult,
+};
+
+/// Env var that switches vendored output to verify mode, e.g. in CI
+pub const VERIFY_ENV_VAR: &str = "ELF_MAGIC_VERIFY";
+
+/// Hashes of the vendored code and every embedded program, checked in next to the code
+///
+/// Holds no absolute paths, so it's identical across machines for identical builds.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HashManifest {
+    pub generated_sha256: String,
+    pub programs: BTreeMap<String, ProgramHash>,
+}
+
+/// Hash of a single embedded program
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ProgramHash {
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl HashManifest {
+    pub fn new(code: &str, build_result: &ProgramBuildResult) -> Result<Self, Error> {
+        let mut programs = BTreeMap::new();
+
+        for (program, so_path) in &build_result.successful {
+            let bytes = fs::read(so_path).map_err(|e| {
+                let message = format!("Failed to read {}: {}", so_path.display(), e);
+                Error::CodeGeneration(message)
+            })?;
+
+            programs.insert(
+                program.qualified_name(),
+                ProgramHash {
+                    sha256: sha256_hex(&bytes),
+                    size: bytes.len() as u64,
+                },
+            );
+        }
+
+        Ok(Self {
+            generated_sha256: sha256_hex(code.as_bytes()),
+            programs,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self)
+            .map(|json| json + "\n")
+            .map_err(|e| {
+                let message = format!("Failed to serialize hash manifest: {}", e);
+                Error::CodeGeneration(message)
+            })
+    }
+
+    /// Describe how `checked_in` differs from this manifest, one line per program
+    fn diff(&self, checked_in: &HashManifest) -> Vec<String> {
+        let mut changes = Vec::new();
+
+        for (name, hash) in &self.programs {
+            match checked_in.programs.get(name) {
+                None => changes.push(format!("  + {} (not in checked-in manifest)", name)),
+                Some(old) if old != hash => changes.push(format!(
+                    "  ~ {} (sha256 {} -> {})",
+                    name, old.sha256, hash.sha256
+                )),
+                Some(_) => {}
+            }
+        }
+
+        for name in checked_in.programs.keys() {
+            if !self.programs.contains_key(name) {
+                changes.push(format!("  - {} (no longer embedded)", name));
+            }
+        }
+
+        changes
+    }
+}
+
+/// Write the generated code and its hash manifest into `src/`, or verify the checked-in copies
+pub fn sync(
+    manifest_dir: &Path,
+    vendor: &VendorConfig,
+    code: &str,
+    build_result: &ProgramBuildResult,
+) -> Result<(), Error> {
+    println!("cargo:rerun-if-env-changed={}", VERIFY_ENV_VAR);
+
+    let code_path = resolve_path(manifest_dir, &vendor.path)?;
+    let hashes_path = hash_manifest_path(&code_path);
+    let code = &skip_rustfmt(code);
+    let hashes = HashManifest::new(code, build_result)?;
+
+    if vendor.verify || verify_requested() {
+        return verify(vendor, &code_path, &hashes_path, code, &hashes);
+    }
+
+    let write = |path: &Path, contents: &str| {
+        write_if_changed(path, contents.as_bytes()).map_err(|e| {
+            let message = format!("Failed to write {}: {}", path.display(), e);
+            Error::CodeGeneration(message)
+        })
+    };
+    write(&code_path, code)?;
+    write(&hashes_path, &hashes.to_json()?)?;
+
+    Ok(())
+}
+
+/// Mark the vendored module so `cargo fmt` leaves it alone
+///
+/// rustfmt doesn't leave prettyplease output unchanged, and a reformatted file would
+/// fail verification. The attribute goes after the `//` header, which must stay first.
+fn skip_rustfmt(code: &str) -> String {
+    let (header, body) = split_header(code);
+    format!("{}#![cfg_attr(rustfmt, rustfmt_skip)]\n{}", header, body)
+}
+
+/// Compare freshly generated output against the checked-in files
+fn verify(
+    vendor: &VendorConfig,
+    code_path: &Path,
+    hashes_path: &Path,
+    code: &str,
+    hashes: &HashManifest,
+) -> Result<(), Error> {
+    let mut drift = Vec::new();
+
+    match fs::read_to_string(code_path) {
+        Ok(checked_in) if checked_in == code => {}
+        Ok(_) => drift.push(format!("{} differs", vendor.path.display())),
+        Err(_) => drift.push(format!("{} is missing", vendor.path.display())),
+    }
+
+    let hashes_name = hash_manifest_path(&vendor.path);
+    match fs::read_to_string(hashes_path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<HashManifest>(&json).ok())
+    {
+        Some(checked_in) if &checked_in == hashes => {}
+        Some(checked_in) => {
+            drift.push(format!("{} differs", hashes_name.display()));
+            drift.extend(hashes.diff(&checked_in));
+        }
+        None => drift.push(format!(
+            "{} is missing or unreadable",
+            hashes_name.display()
+        )),
+    }
+
+    if drift.is_empty() {
+        return Ok(());
+    }
+
+    Err(Error::VendorDrift(format!(
+        "\n{}\nRebuild without verify (and without {}) to regenerate the checked-in files",
+        drift.join("\n"),
+        VERIFY_ENV_VAR
+    )))
+}
+
+/// Whether `ELF_MAGIC_VERIFY` is set to anything but empty or `0`
+fn verify_requested() -> bool {
+    env::var_os(VERIFY_ENV_VAR).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Resolve the vendored code path, which must be a `.rs` file under the ELF crate's `src/`
+fn resolve_path(manifest_dir: &Path, path: &Path) -> Result<PathBuf, Error> {
+    let mut components = path.components();
+    let under_src = components.next() == Some(Component::Normal("src".as_ref()))
+        && components.all(|c| matches!(c, Component::Normal(_)))
+        && path.extension().is_some_and(|ext| ext == "rs");
+
+    if !under_src {
+        return Err(Error::Config(format!(
+            "vendor.path '{}' must be a relative .rs path under src/ (e.g. \"src/elves.rs\")",
+            path.display()
+        )));
+    }
+
+    Ok(manifest_dir.join(path))
+}
+
+/// The hash manifest lives next to the code: `src/elves.rs` -> `src/elves.hashes.json`
+fn hash_manifest_path(code_path: &Path) -> PathBuf {
+    code_path.with_extension("hashes.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::{BuildTarget, SolanaProgram};
+    use tempfile::TempDir;
+
+    fn sample_result(temp_dir: &Path, so_bytes: &[u8]) -> ProgramBuildResult {
+        let so_path = temp_dir.join("vault.so");
+        fs::write(&so_path, so_bytes).unwrap();
+
+        let vault = SolanaProgram {
+            package_name: "vault".to_string(),
+            target_name: "vault".to_string(),
+            build_target: BuildTarget::Lib("vault".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
+            manifest_path: PathBuf::from("/repo/vault/Cargo.toml"),
+            constant_name: "VAULT_ELF".to_string(),
+        };
+
+        ProgramBuildResult {
+            successful: vec![(vault, so_path)],
+            failed: Vec::new(),
+        }
+    }
+
+    fn vendor_config(verify: bool) -> VendorConfig {
+        VendorConfig {
+            path: PathBuf::from("src/elves.rs"),
+            verify,
+        }
+    }
+
+    #[test]
+    fn test_resolve_path_requires_src() {
+        let manifest_dir = Path::new("/repo/elves");
+
+        assert_eq!(
+            resolve_path(manifest_dir, Path::new("src/generated/elves.rs")).unwrap(),
+            PathBuf::from("/repo/elves/src/generated/elves.rs")
+        );
+
+        for path in [
+            "elves.rs",
+            "/repo/elves/src/elves.rs",
+            "src/../elves.rs",
+            "src/elves.txt",
+        ] {
+            let error = resolve_path(manifest_dir, Path::new(path)).unwrap_err();
+            assert!(error
+                .to_string()
+                .contains("must be a relative .rs path under src/"));
+        }
+    }
+
+    #[test]
+    fn test_hash_manifest_path() {
+        assert_eq!(
+            hash_manifest_path(Path::new("src/elves.rs")),
+            PathBuf::from("src/elves.hashes.json")
+        );
+    }
+
+    #[test]
+    fn test_sync_writes_then_verifies() {
+        let temp_dir = TempDir::new().unwrap();
+        let build_result = sample_result(temp_dir.path(), b"\x7fELF");
+
+        sync(
+            temp_dir.path(),
+            &vendor_config(false),
+            "// code\n",
+            &build_result,
+        )
+        .unwrap();
+
+        let code = fs::read_to_string(temp_dir.path().join("src/elves.rs")).unwrap();
+        assert_eq!(code, "// code\n#![cfg_attr(rustfmt, rustfmt_skip)]\n");
+
+        let hashes = fs::read_to_string(temp_dir.path().join("src/elves.hashes.json")).unwrap();
+        let hashes: HashManifest = serde_json::from_str(&hashes).unwrap();
+        assert_eq!(hashes.programs["vault"].size, 4);
+
+        // Unchanged output verifies cleanly
+        sync(
+            temp_dir.path(),
+            &vendor_config(true),
+            "// code\n",
+            &build_result,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_skip_rustfmt_follows_header() {
+        assert_eq!(
+            skip_rustfmt("// @generated by elf-magic\n// Build Status:\n/// Docs\npub struct Elf;\n"),
+            "// @generated by elf-magic\n// Build Status:\n#![cfg_attr(rustfmt, rustfmt_skip)]\n/// Docs\npub struct Elf;\n"
+        );
+        assert_eq!(
+            skip_rustfmt("pub struct Elf;\n"),
+            "#![cfg_attr(rustfmt, rustfmt_skip)]\npub struct Elf;\n"
+        );
+    }
+
+    #[test]
+    fn test_verify_reports_drift() {
+        let temp_dir = TempDir::new().unwrap();
+        let build_result = sample_result(temp_dir.path(), b"\x7fELF");
+        sync(
+            temp_dir.path(),
+            &vendor_config(false),
+            "// code\n",
+            &build_result,
+        )
+        .unwrap();
+
+        // The program changed since the files were checked in
+        let rebuilt = sample_result(temp_dir.path(), b"\x7fELF2");
+        let error = sync(temp_dir.path(), &vendor_config(true), "// code\n", &rebuilt)
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.starts_with("Checked-in generated code is out of date"));
+        assert!(!error.contains("src/elves.rs differs"));
+        assert!(error.contains("src/elves.hashes.json differs"));
+        assert!(error.contains("  ~ vault (sha256 "));
+
+        // Verify never writes
+        let hashes = fs::read_to_string(temp_dir.path().join("src/elves.hashes.json")).unwrap();
+        assert!(hashes.contains("\"size\": 4"));
+    }
+
+    #[test]
+    fn test_verify_reports_missing_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let build_result = sample_result(temp_dir.path(), b"\x7fELF");
+
+        let error = sync(
+            temp_dir.path(),
+            &vendor_config(true),
+            "// code\n",
+            &build_result,
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(error.contains("src/elves.rs is missing"));
+        assert!(error.contains("src/elves.hashes.json is missing or unreadable"));
+    }
+}
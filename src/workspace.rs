@@ -1,14 +1,25 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fs,
     path::{Path, PathBuf},
+    process::Command,
 };
 
-use cargo_metadata::{CrateType, Metadata, MetadataCommand};
+use cargo_metadata::{
+    CrateType, DependencyKind, Metadata, MetadataCommand, Package, PackageId, Target,
+};
 
 use crate::{
-    config::{resolve_constants_paths, resolve_targets_paths, Config},
+    config::{
+        lookup_override, resolve_config_path, resolve_constants_paths, resolve_program_ids_paths,
+        resolve_targets_paths, BuildSettings, Config, FilterPattern, Layout, MatchField, Matcher,
+        OverrideKey, ProgramMetadata,
+    },
     error::Error,
-    programs::{DiscoveredPrograms, SolanaProgram},
+    programs::{
+        decode_program_id, BuildTarget, Decision, DiscoveredPrograms, FilterRule, PatternOrigin,
+        PatternUsage, SkipReason, SolanaProgram,
+    },
 };
 
 /// Load workspaces from config
@@ -16,34 +27,66 @@ pub fn load_workspaces(config_file_dir: &Path, config: &Config) -> Result<Vec<Wo
     // Get resolved overrides from config
     let resolved_constants = resolve_constants_paths(&config.constants(), config_file_dir);
     let resolved_targets = resolve_targets_paths(&config.targets(), config_file_dir);
-
-    match config {
-        Config::LaserEyes { workspaces, .. } => {
+    let resolved_program_ids = resolve_program_ids_paths(&config.program_ids(), config_file_dir);
+    check_program_ids(&resolved_program_ids)?;
+    let namespaced = config.layout() == Layout::Namespaced;
+    let elves_crate_dir =
+        fs::canonicalize(config_file_dir).unwrap_or_else(|_| config_file_dir.to_path_buf());
+
+    let workspaces = match config {
+        Config::LaserEyes {
+            workspaces,
+            global_only,
+            global_deny,
+            ..
+        } => {
             let mut results = Vec::new();
             for workspace in workspaces {
-                let metadata = MetadataCommand::new()
-                    .manifest_path(&workspace.manifest_path)
-                    .no_deps()
-                    .other_options(vec!["--locked".to_string()])
-                    .exec()?;
-
-                let manifest_path = workspace.manifest_path.clone();
-
-                results.push(Workspace {
-                    metadata,
-                    manifest_path,
-                    filter_mode: FilterMode::Only(workspace.only.clone()),
-                    constants_overrides: resolved_constants.clone(),
-                    targets_overrides: resolved_targets.clone(),
-                });
+                let found = find_workspaces(
+                    config_file_dir,
+                    workspace.manifest_path.as_deref(),
+                    workspace.scan.as_deref(),
+                    &workspace.build,
+                )?;
+
+                let filter_mode = FilterMode::from_lists(
+                    with_origin(global_only, PatternOrigin::GlobalOnly),
+                    Some(with_origin(&workspace.only, PatternOrigin::Only)),
+                    merge_patterns(global_deny, &workspace.deny, PatternOrigin::Deny),
+                );
+
+                for (manifest_path, metadata) in found {
+                    let alias = namespaced
+                        .then(|| {
+                            resolve_workspace_alias(
+                                workspace.alias.as_deref(),
+                                config_file_dir,
+                                &manifest_path,
+                            )
+                        })
+                        .transpose()?;
+
+                    results.push(Workspace {
+                        metadata,
+                        manifest_path,
+                        alias,
+                        filter_mode: filter_mode.clone(),
+                        constants_overrides: resolved_constants.clone(),
+                        targets_overrides: resolved_targets.clone(),
+                        program_ids_overrides: resolved_program_ids.clone(),
+                        build: resolve_build_settings(&workspace.build, config_file_dir),
+                        elves_crate_dir: elves_crate_dir.clone(),
+                    });
+                }
             }
-            Ok(results)
+            results
         }
         Config::Magic => {
-            let metadata = MetadataCommand::new()
-                .no_deps()
-                .other_options(vec!["--locked".to_string()])
-                .exec()?;
+            // The workspace the ELF crate belongs to, wherever cargo runs the build script from
+            let metadata = load_metadata(
+                &config_file_dir.join("Cargo.toml"),
+                &BuildSettings::default(),
+            )?;
 
             let manifest_path = metadata
                 .workspace_root
@@ -52,64 +95,582 @@ pub fn load_workspaces(config_file_dir: &Path, config: &Config) -> Result<Vec<Wo
                 .display()
                 .to_string();
 
-            Ok(vec![Workspace {
+            vec![Workspace {
                 metadata,
                 manifest_path,
+                alias: None,
                 filter_mode: FilterMode::Magic,
                 constants_overrides: resolved_constants,
                 targets_overrides: resolved_targets,
-            }])
+                program_ids_overrides: resolved_program_ids,
+                build: BuildSettings::default(),
+                elves_crate_dir,
+            }]
         }
         Config::Permissive {
             workspaces,
             global_deny,
+            global_only,
             ..
         } => {
             let mut results = Vec::new();
             for workspace in workspaces {
-                let metadata = MetadataCommand::new()
-                    .manifest_path(&workspace.manifest_path)
-                    .no_deps()
-                    .other_options(vec!["--locked".to_string()])
-                    .exec()?;
-
-                let manifest_path = workspace.manifest_path.clone();
-
-                // Merge global excludes with workspace-specific excludes
-                let mut merged_denies = global_deny.clone();
-                merged_denies.extend(workspace.deny.clone());
-
-                results.push(Workspace {
-                    metadata,
-                    manifest_path,
-                    filter_mode: FilterMode::Deny(merged_denies),
-                    constants_overrides: resolved_constants.clone(),
-                    targets_overrides: resolved_targets.clone(),
-                });
+                let found = find_workspaces(
+                    config_file_dir,
+                    workspace.manifest_path.as_deref(),
+                    workspace.scan.as_deref(),
+                    &workspace.build,
+                )?;
+
+                // Without any `only` patterns every program starts out included
+                let filter_mode = FilterMode::from_lists(
+                    with_origin(global_only, PatternOrigin::GlobalOnly),
+                    workspace
+                        .only
+                        .as_deref()
+                        .map(|only| with_origin(only, PatternOrigin::Only)),
+                    merge_patterns(global_deny, &workspace.deny, PatternOrigin::Deny),
+                );
+
+                for (manifest_path, metadata) in found {
+                    let alias = namespaced
+                        .then(|| {
+                            resolve_workspace_alias(
+                                workspace.alias.as_deref(),
+                                config_file_dir,
+                                &manifest_path,
+                            )
+                        })
+                        .transpose()?;
+
+                    results.push(Workspace {
+                        metadata,
+                        manifest_path,
+                        alias,
+                        filter_mode: filter_mode.clone(),
+                        constants_overrides: resolved_constants.clone(),
+                        targets_overrides: resolved_targets.clone(),
+                        program_ids_overrides: resolved_program_ids.clone(),
+                        build: resolve_build_settings(&workspace.build, config_file_dir),
+                        elves_crate_dir: elves_crate_dir.clone(),
+                    });
+                }
             }
-            Ok(results)
+            results
+        }
+    };
+
+    check_unique_aliases(&workspaces)?;
+
+    Ok(workspaces)
+}
+
+/// Global patterns followed by a workspace's own, each tagged with where it came from
+fn merge_patterns(
+    global: &[FilterPattern],
+    local: &[FilterPattern],
+    origin: PatternOrigin,
+) -> Vec<(FilterPattern, PatternOrigin)> {
+    let global_origin = match origin {
+        PatternOrigin::Only | PatternOrigin::GlobalOnly => PatternOrigin::GlobalOnly,
+        PatternOrigin::Deny | PatternOrigin::GlobalDeny => PatternOrigin::GlobalDeny,
+    };
+    let mut patterns = with_origin(global, global_origin);
+    patterns.extend(with_origin(local, origin));
+    patterns
+}
+
+/// Load the workspace(s) a config entry points at
+///
+/// A plain `manifest_path` is loaded as-is. A glob `manifest_path` or a `scan` directory can
+/// match several manifests; each is loaded through the workspace it belongs to, so members
+/// of one workspace only produce it once.
+fn find_workspaces(
+    config_file_dir: &Path,
+    manifest_path: Option<&str>,
+    scan: Option<&str>,
+    build: &BuildSettings,
+) -> Result<Vec<(String, Metadata)>, Error> {
+    let candidates = match (manifest_path, scan) {
+        (Some(manifest_path), None) if !is_glob(manifest_path) => {
+            let metadata = load_metadata(&resolve_config_path(config_file_dir, manifest_path), build)?;
+            return Ok(vec![(manifest_path.to_string(), metadata)]);
+        }
+        (Some(pattern), None) => expand_manifest_glob(config_file_dir, pattern)?,
+        (None, Some(scan)) => scan_workspace_roots(config_file_dir, scan)?,
+        (Some(manifest_path), Some(scan)) => {
+            return Err(Error::Config(format!(
+                "Workspace entry sets both manifest_path = \"{}\" and scan = \"{}\"; use one or the other",
+                manifest_path, scan
+            )))
+        }
+        (None, None) => {
+            return Err(Error::Config(
+                "Workspace entry needs a `manifest_path` or a `scan` directory".to_string(),
+            ))
+        }
+    };
+
+    load_candidates(config_file_dir, candidates, |manifest_path| {
+        load_metadata(manifest_path, build)
+    })
+}
+
+/// Load the workspace behind each candidate manifest, once per workspace
+///
+/// Candidates already known as the root or a member of a loaded workspace are skipped
+/// without running `cargo metadata` on them again.
+fn load_candidates(
+    config_file_dir: &Path,
+    candidates: Vec<PathBuf>,
+    mut load: impl FnMut(&Path) -> Result<Metadata, Error>,
+) -> Result<Vec<(String, Metadata)>, Error> {
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut known_manifests = HashSet::new();
+    let mut seen_roots = HashSet::new();
+    let mut found = Vec::new();
+    for candidate in candidates {
+        let candidate_path = resolve_config_path(config_file_dir, &candidate);
+        if known_manifests.contains(&candidate_path) {
+            continue;
+        }
+
+        let metadata = load(&candidate_path)?;
+        let root_manifest = metadata.workspace_root.as_std_path().join("Cargo.toml");
+        known_manifests.insert(canonical(&root_manifest));
+        known_manifests.extend(
+            metadata
+                .workspace_packages()
+                .iter()
+                .map(|package| canonical(package.manifest_path.as_std_path())),
+        );
+        if !seen_roots.insert(root_manifest.clone()) {
+            continue;
+        }
+
+        // A member crate stands in for the workspace it belongs to
+        let is_root = candidate_path == canonical(&root_manifest);
+        let manifest_path = if is_root {
+            candidate.display().to_string()
+        } else {
+            root_manifest.display().to_string()
+        };
+        found.push((manifest_path, metadata));
+    }
+
+    Ok(found)
+}
+
+fn load_metadata(manifest_path: &Path, build: &BuildSettings) -> Result<Metadata, Error> {
+    let metadata = MetadataCommand::new()
+        .manifest_path(manifest_path)
+        .no_deps()
+        .other_options(build.cargo_flags())
+        .exec()?;
+    ensure_lockfile(&metadata, build)?;
+    Ok(metadata)
+}
+
+/// Make sure a workspace that will be built with `--locked`/`--frozen` has a Cargo.lock
+///
+/// `cargo metadata --no-deps` never looks at the lockfile, so without this check a missing
+/// one only surfaces halfway through `cargo build-sbf`.
+fn ensure_lockfile(metadata: &Metadata, build: &BuildSettings) -> Result<(), Error> {
+    let root = metadata.workspace_root.as_std_path();
+    if root.join("Cargo.lock").exists() || !build.requires_lockfile() {
+        return Ok(());
+    }
+
+    if !build.generate_lockfile {
+        return Err(Error::Config(format!(
+            "{} has no Cargo.lock, but the workspace is built with `{}`; commit one, or set \
+             `generate_lockfile = true` or `locked = false` in its `build` table",
+            root.display(),
+            if build.frozen { "frozen" } else { "locked" }
+        )));
+    }
+
+    let mut command = Command::new("cargo");
+    command
+        .arg("generate-lockfile")
+        .arg("--manifest-path")
+        .arg(root.join("Cargo.toml"));
+    if build.offline || build.frozen {
+        command.arg("--offline");
+    }
+
+    let output = command.output().map_err(|e| {
+        Error::WorkspaceDiscovery(format!("Failed to run cargo generate-lockfile: {}", e))
+    })?;
+    if !output.status.success() {
+        return Err(Error::WorkspaceDiscovery(format!(
+            "cargo generate-lockfile failed for {}: {}",
+            root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+/// Manifests matching a `manifest_path` glob, relative to the config's directory
+fn expand_manifest_glob(config_file_dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, Error> {
+    let full_pattern = config_file_dir.join(pattern);
+    let paths = glob::glob(&full_pattern.to_string_lossy())
+        .map_err(|e| Error::Config(format!("Invalid manifest_path glob \"{}\": {}", pattern, e)))?;
+
+    let mut manifests: Vec<PathBuf> = paths
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .map(|path| relative_to(config_file_dir, path))
+        .collect();
+    manifests.sort();
+
+    if manifests.is_empty() {
+        return Err(Error::Config(format!(
+            "manifest_path glob \"{}\" matched no Cargo.toml files",
+            pattern
+        )));
+    }
+
+    Ok(manifests)
+}
+
+/// Workspace root manifests under a `scan` directory, relative to the config's directory
+///
+/// A branch of the tree stops at a package's `Cargo.toml`: whatever sits below it belongs to
+/// that package's workspace. Below a `[workspace]` root the scan goes on, but only picks up
+/// independent roots: manifests with a `[workspace]` of their own, or packages the root's
+/// `exclude` leaves out. Hidden directories and `target` directories are never entered.
+fn scan_workspace_roots(config_file_dir: &Path, scan: &str) -> Result<Vec<PathBuf>, Error> {
+    let scan_dir = config_file_dir.join(scan);
+    if !scan_dir.is_dir() {
+        return Err(Error::Config(format!(
+            "scan directory \"{}\" does not exist",
+            scan
+        )));
+    }
+
+    let mut manifests = Vec::new();
+    let mut excluded: Vec<PathBuf> = Vec::new();
+    // Each directory, and whether it sits below a workspace root the scan already found
+    let mut pending = vec![(scan_dir, false)];
+    while let Some((dir, mut in_workspace)) = pending.pop() {
+        let manifest = dir.join("Cargo.toml");
+        if manifest.is_file() {
+            let workspace = fs::read_to_string(&manifest)
+                .ok()
+                .and_then(|content| toml::from_str::<toml::Value>(&content).ok())
+                .and_then(|manifest| manifest.get("workspace").cloned());
+            let is_excluded = excluded.iter().any(|excluded| dir.starts_with(excluded));
+            if !in_workspace || workspace.is_some() || is_excluded {
+                manifests.push(relative_to(config_file_dir, manifest));
+            }
+
+            // Members are loaded through their root; nested roots are found by going on
+            let Some(workspace) = workspace else {
+                continue;
+            };
+            let exclude = workspace.get("exclude").and_then(|e| e.as_array());
+            excluded.extend(
+                exclude
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|path| path.as_str())
+                    .map(|path| dir.join(path)),
+            );
+            in_workspace = true;
+        }
+
+        let entries = fs::read_dir(&dir).map_err(|e| {
+            Error::WorkspaceDiscovery(format!("Failed to scan {}: {}", dir.display(), e))
+        })?;
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || name == "target" {
+                continue;
+            }
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                pending.push((entry.path(), in_workspace));
+            }
+        }
+    }
+    manifests.sort();
+
+    if manifests.is_empty() {
+        return Err(Error::Config(format!(
+            "scan directory \"{}\" contains no Cargo.toml files",
+            scan
+        )));
+    }
+
+    Ok(manifests)
+}
+
+fn relative_to(base: &Path, path: PathBuf) -> PathBuf {
+    path.strip_prefix(base)
+        .map(Path::to_path_buf)
+        .unwrap_or(path)
+}
+
+/// Anchor a workspace's `target_dir` on the ELF crate
+fn resolve_build_settings(build: &BuildSettings, config_file_dir: &Path) -> BuildSettings {
+    BuildSettings {
+        target_dir: build
+            .target_dir
+            .as_ref()
+            .map(|dir| resolve_config_path(config_file_dir, dir)),
+        ..build.clone()
+    }
+}
+
+/// Tag each pattern with the config list it came from
+fn with_origin(
+    patterns: &[FilterPattern],
+    origin: PatternOrigin,
+) -> Vec<(FilterPattern, PatternOrigin)> {
+    patterns
+        .iter()
+        .map(|pattern| (pattern.clone(), origin))
+        .collect()
+}
+
+/// Resolve a workspace's module name, defaulting to its directory name
+fn resolve_workspace_alias(
+    alias: Option<&str>,
+    config_file_dir: &Path,
+    manifest_path: &str,
+) -> Result<String, Error> {
+    match alias {
+        Some(alias) if is_valid_module_name(alias) => Ok(alias.to_string()),
+        Some(alias) => Err(Error::Config(format!(
+            "Invalid workspace alias '{}' for {}: must be a lowercase Rust identifier that isn't a keyword or crate name, like '{}'",
+            alias,
+            manifest_path,
+            sanitize_module_name(alias)
+        ))),
+        None => {
+            // Resolved first, so `./Cargo.toml` is named after the directory it points at
+            // and every spelling of one workspace gets the same module name
+            let dir_name = resolve_config_path(config_file_dir, manifest_path)
+                .parent()
+                .and_then(|dir| dir.file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            Ok(sanitize_module_name(&dir_name))
+        }
+    }
+}
+
+/// Turn a directory name into a lowercase Rust module name
+fn sanitize_module_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let sanitized = sanitized.trim_matches('_').to_string();
+
+    if sanitized.is_empty() {
+        "root".to_string()
+    } else if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("ws_{}", sanitized)
+    } else if RESERVED_MODULE_NAMES.contains(&sanitized.as_str()) {
+        format!("{}_workspace", sanitized)
+    } else {
+        sanitized
+    }
+}
+
+/// Keywords, crate names and generated modules a generated `pub mod` must not shadow
+const RESERVED_MODULE_NAMES: &[&str] = &[
+    "harness", "alloc", "as", "async", "await", "break", "const", "continue", "core", "crate",
+    "dyn", "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop",
+    "match", "mod", "move", "mut", "pub", "ref", "return", "self", "static", "std", "struct",
+    "super", "trait", "true", "type", "unsafe", "use", "where", "while", "abstract", "become",
+    "box", "do", "final", "gen", "macro", "override", "priv", "try", "typeof", "unsized",
+    "virtual", "yield",
+];
+
+fn is_valid_module_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && name != "_"
+        && !RESERVED_MODULE_NAMES.contains(&name)
+}
+
+/// Two workspaces in the namespaced layout can't share a module name
+fn check_unique_aliases(workspaces: &[Workspace]) -> Result<(), Error> {
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+
+    for workspace in workspaces {
+        let Some(alias) = workspace.alias.as_deref() else {
+            continue;
+        };
+
+        if let Some(first) = seen.insert(alias, &workspace.manifest_path) {
+            return Err(Error::Config(format!(
+                "Workspaces {} and {} both use alias '{}'; set a distinct `alias` on one of them",
+                first, workspace.manifest_path, alias
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Every configured program ID must be a valid base58 pubkey
+fn check_program_ids(program_ids: &HashMap<OverrideKey, String>) -> Result<(), Error> {
+    for (key, program_id) in program_ids {
+        if decode_program_id(program_id).is_none() {
+            return Err(Error::Config(format!(
+                "Invalid program ID '{}' for {}: expected a base58-encoded 32-byte pubkey",
+                program_id,
+                key.manifest_path.display()
+            )));
         }
     }
+
+    Ok(())
 }
 
 /// Filtering mode for programs
+///
+/// Any `global_only` patterns narrow every mode but magic, ahead of the workspace's own
+/// `only`; each pattern's origin says which list it's on.
 #[derive(Debug, Clone)]
 pub enum FilterMode {
     /// Magic mode: include all programs (no filtering)
     Magic,
-    /// Permissive mode: include all except those matching deny patterns
-    Deny(Vec<String>),
-    /// Laser-eyes mode: include programs matching only patterns
-    Only(Vec<String>),
+    /// No workspace `only`: include all except those matching deny patterns
+    Deny(Vec<(FilterPattern, PatternOrigin)>),
+    /// Laser-eyes mode: include programs matching the workspace's only patterns
+    Only(Vec<(FilterPattern, PatternOrigin)>),
+    /// Both lists: include programs matching an only pattern, minus those matching a deny
+    /// pattern
+    OnlyThenDeny(Vec<(FilterPattern, PatternOrigin)>),
+}
+
+impl FilterMode {
+    /// Filter by the workspace's `only` patterns when there are any (even an empty list),
+    /// then by `deny`; `global_only` narrows on its own whenever it's non-empty
+    fn from_lists(
+        global_only: Vec<(FilterPattern, PatternOrigin)>,
+        only: Option<Vec<(FilterPattern, PatternOrigin)>>,
+        deny: Vec<(FilterPattern, PatternOrigin)>,
+    ) -> Self {
+        let mut patterns = global_only;
+        match only {
+            None => {
+                patterns.extend(deny);
+                FilterMode::Deny(patterns)
+            }
+            Some(only) if deny.is_empty() => {
+                patterns.extend(only);
+                FilterMode::Only(patterns)
+            }
+            Some(only) => {
+                patterns.extend(only);
+                patterns.extend(deny);
+                FilterMode::OnlyThenDeny(patterns)
+            }
+        }
+    }
+
+    /// Every pattern this mode filters with, and where it was configured
+    fn patterns(&self) -> &[(FilterPattern, PatternOrigin)] {
+        match self {
+            FilterMode::Magic => &[],
+            FilterMode::Deny(patterns)
+            | FilterMode::Only(patterns)
+            | FilterMode::OnlyThenDeny(patterns) => patterns,
+        }
+    }
+
+    /// Decide on a program, also reporting which of [`Self::patterns`] it counts as a match for
+    ///
+    /// A deny pattern only counts when the program was still included after `only`, so a deny
+    /// pattern that never changes an outcome shows up as unused.
+    fn apply(&self, program: &SolanaProgram) -> (Decision, Vec<bool>) {
+        let patterns = self.patterns();
+        let matched: Vec<bool> = patterns
+            .iter()
+            .map(|(pattern, _)| matches_filter_pattern(program, pattern))
+            .collect();
+        let first_match = |only: bool| {
+            patterns
+                .iter()
+                .zip(&matched)
+                .find(|((_, origin), matched)| **matched && origin.is_only() == only)
+                .map(|((pattern, origin), _)| (pattern.to_string(), *origin))
+        };
+
+        // `global_only` and the workspace's `only` each narrow on their own, so a program
+        // has to match both lists when both are there
+        let in_list = |list: PatternOrigin| {
+            patterns
+                .iter()
+                .zip(&matched)
+                .any(|((_, origin), matched)| *origin == list && *matched)
+        };
+        let has_global_only = patterns
+            .iter()
+            .any(|(_, origin)| *origin == PatternOrigin::GlobalOnly);
+        let has_only = matches!(self, FilterMode::Only(_) | FilterMode::OnlyThenDeny(_));
+        let not_in_only = (has_global_only && !in_list(PatternOrigin::GlobalOnly))
+            || (has_only && !in_list(PatternOrigin::Only));
+
+        let decision = match (self, first_match(true), first_match(false)) {
+            // Magic mode: include all programs
+            (FilterMode::Magic, ..) => Decision::by_rule(FilterRule::IncludeAll),
+            // Only patterns narrow the workspace first
+            _ if not_in_only => Decision::by_rule(FilterRule::NotInOnly),
+            // Then deny patterns exclude from what's left
+            (_, _, Some((pattern, origin))) => {
+                Decision::by_pattern(FilterRule::Denied, pattern, origin)
+            }
+            (_, Some((pattern, origin)), None) => {
+                Decision::by_pattern(FilterRule::OnlyMatched, pattern, origin)
+            }
+            (_, None, None) => Decision::by_rule(FilterRule::NotDenied),
+        };
+
+        let excluded_by_only = decision.rule == FilterRule::NotInOnly;
+        let counted = patterns
+            .iter()
+            .zip(matched)
+            .map(|((_, origin), matched)| matched && (origin.is_only() || !excluded_by_only))
+            .collect();
+        (decision, counted)
+    }
 }
 
 /// Information about an individual cargo workspace
 pub struct Workspace {
     pub metadata: Metadata,
     pub manifest_path: String,
+    /// Module name in the namespaced layout
+    pub alias: Option<String>,
     pub filter_mode: FilterMode,
-    pub constants_overrides: HashMap<PathBuf, String>,
-    pub targets_overrides: HashMap<PathBuf, String>,
+    pub constants_overrides: HashMap<OverrideKey, String>,
+    pub targets_overrides: HashMap<OverrideKey, String>,
+    pub program_ids_overrides: HashMap<OverrideKey, String>,
+    /// How the workspace's programs are built, before program-side settings
+    pub build: BuildSettings,
+    /// Canonical directory of the ELF crate running the build script
+    pub elves_crate_dir: PathBuf,
 }
 
 impl Workspace {
@@ -117,139 +678,336 @@ impl Workspace {
     pub fn discover_programs(&self) -> Result<DiscoveredPrograms, Error> {
         let mut included = Vec::new();
         let mut excluded = Vec::new();
-
+        let mut skipped = Vec::new();
+        let mut pattern_usage: Vec<PatternUsage> = self
+            .filter_mode
+            .patterns()
+            .iter()
+            .map(|(pattern, origin)| PatternUsage {
+                pattern: pattern.to_string(),
+                origin: *origin,
+                matches: 0,
+            })
+            .collect();
+
+        let elves_crate_packages = self.elves_crate_packages();
         for package in &self.metadata.packages {
-            for target in &package.targets {
-                let is_cdylib = target.crate_types.contains(&CrateType::CDyLib);
-                if !is_cdylib {
-                    continue;
-                }
+            let is_cdylib = |target: &Target| target.crate_types.contains(&CrateType::CDyLib);
+            if !package.targets.iter().any(is_cdylib) {
+                continue;
+            }
 
-                let manifest_path = package.manifest_path.as_std_path().to_path_buf();
+            // Canonical, like the override keys it's looked up against
+            let manifest_path = package.manifest_path.as_std_path();
+            let manifest_path =
+                fs::canonicalize(manifest_path).unwrap_or_else(|_| manifest_path.to_path_buf());
+
+            // Only other cdylib packages are asked for program-side settings, so the ELF
+            // crate's own [package.metadata.elf-magic] never gets parsed as one
+            let elves_crate_skip = elves_crate_packages.get(&package.id).copied();
+            let package_config = match elves_crate_skip {
+                Some(_) => ProgramMetadata::default(),
+                None => ProgramMetadata::from_package_metadata(&package.metadata, &manifest_path)?,
+            };
+            let is_program = is_solana_program(package, &package_config);
+
+            for target in package.targets.iter().filter(|target| is_cdylib(target)) {
                 let base_target_name = target.name.to_string();
+                let build_target = if target.is_example() {
+                    BuildTarget::Example {
+                        name: base_target_name.clone(),
+                        target_directory: self
+                            .build
+                            .target_dir
+                            .clone()
+                            .unwrap_or_else(|| self.metadata.target_directory.clone().into()),
+                    }
+                } else {
+                    BuildTarget::Lib(base_target_name.clone())
+                };
 
                 // Create fully resolved program upfront
-                let program = SolanaProgram {
+                let mut program = SolanaProgram {
                     package_name: package.name.to_string(),
                     target_name: resolve_target_name(
                         &base_target_name,
                         &manifest_path,
                         &self.targets_overrides,
+                        package_config.target.as_deref(),
                     ),
                     manifest_path: manifest_path.clone(),
                     constant_name: resolve_constant_name(
                         &base_target_name,
                         &manifest_path,
                         &self.constants_overrides,
+                        package_config.constant.as_deref(),
                     ),
+                    program_id: resolve_program_id(
+                        &base_target_name,
+                        &manifest_path,
+                        &self.program_ids_overrides,
+                        target.src_path.as_std_path(),
+                    ),
+                    build: self.build.for_program(&package_config),
+                    tags: package_config.tags.clone(),
+                    decision: Decision::default(),
+                    build_target,
+                    workspace_alias: self.alias.clone(),
                 };
 
+                // Building these would run this very build script again, from inside itself
+                if let Some(reason) = elves_crate_skip {
+                    skipped.push((program, reason));
+                    continue;
+                }
+
+                // wasm bindings, C FFI libraries and the like are cdylibs too
+                if !is_program {
+                    skipped.push((program, SkipReason::NotAProgram));
+                    continue;
+                }
+
+                if package_config.skip {
+                    skipped.push((program, SkipReason::SkippedByPackage));
+                    continue;
+                }
+
                 // Now filter the fully resolved program
-                match &self.filter_mode {
-                    FilterMode::Magic => {
-                        // Magic mode: include all programs
-                        included.push(program);
-                    }
-                    FilterMode::Deny(deny_patterns) => {
-                        // Permissive mode: include all except those matching deny patterns
-                        if should_include_program_permissive(&program, deny_patterns) {
-                            included.push(program);
-                        } else {
-                            excluded.push(program);
-                        }
-                    }
-                    FilterMode::Only(only_patterns) => {
-                        // Laser-eyes mode: include programs matching only patterns
-                        if should_only_include_program(&program, only_patterns) {
-                            included.push(program);
-                        } else {
-                            excluded.push(program);
-                        }
-                    }
+                let (decision, matched) = self.filter_mode.apply(&program);
+
+                // Count what each pattern matched, so patterns that match nothing can be reported
+                for (usage, matched) in pattern_usage.iter_mut().zip(matched) {
+                    usage.matches += usize::from(matched);
+                }
+
+                program.decision = decision;
+                if program.decision.is_included() {
+                    included.push(program);
+                } else {
+                    excluded.push(program);
                 }
             }
         }
 
         included.sort_by(|a, b| a.target_name.cmp(&b.target_name));
         excluded.sort_by(|a, b| a.target_name.cmp(&b.target_name));
+        skipped.sort_by(|(a, _), (b, _)| a.target_name.cmp(&b.target_name));
 
         Ok(DiscoveredPrograms {
             workspace_path: self.manifest_path.clone(),
+            workspace_alias: self.alias.clone(),
             included,
             excluded,
+            skipped,
+            pattern_usage,
         })
     }
+
+    /// The ELF crate and every package here that depends on it, normally or to build
+    fn elves_crate_packages(&self) -> HashMap<&PackageId, SkipReason> {
+        let package_dirs: Vec<(&PackageId, PathBuf)> = self
+            .metadata
+            .packages
+            .iter()
+            .filter_map(|package| {
+                let dir = package.manifest_path.parent()?.as_std_path();
+                Some((&package.id, fs::canonicalize(dir).ok()?))
+            })
+            .collect();
+
+        let mut found: HashMap<&PackageId, SkipReason> = package_dirs
+            .iter()
+            .filter(|(_, dir)| *dir == self.elves_crate_dir)
+            .map(|(id, _)| (*id, SkipReason::ElvesCrate))
+            .collect();
+
+        // Walk reverse dependencies until nothing new turns up
+        loop {
+            let found_dirs: HashSet<&PathBuf> = package_dirs
+                .iter()
+                .filter(|(id, _)| found.contains_key(id))
+                .map(|(_, dir)| dir)
+                .collect();
+
+            let dependents: Vec<&PackageId> = self
+                .metadata
+                .packages
+                .iter()
+                .filter(|package| !found.contains_key(&package.id))
+                .filter(|package| {
+                    package.dependencies.iter().any(|dependency| {
+                        dependency.kind != DependencyKind::Development
+                            && dependency
+                                .path
+                                .as_ref()
+                                .and_then(|path| fs::canonicalize(path).ok())
+                                .is_some_and(|path| found_dirs.contains(&path))
+                    })
+                })
+                .map(|package| &package.id)
+                .collect();
+
+            if dependents.is_empty() {
+                return found;
+            }
+            found.extend(
+                dependents
+                    .into_iter()
+                    .map(|id| (id, SkipReason::DependsOnElvesCrate)),
+            );
+        }
+    }
+}
+
+/// Crates that only Solana programs depend on
+const SOLANA_PROGRAM_CRATES: &[&str] = &[
+    "solana-program",
+    "solana-program-entrypoint",
+    "solana-nostd-entrypoint",
+    "pinocchio",
+    "anchor-lang",
+    "steel",
+];
+
+/// Whether a package's cdylib targets are Solana programs
+///
+/// `[package.metadata.elf-magic] program = true|false` wins; otherwise a normal
+/// dependency on a Solana program crate decides.
+fn is_solana_program(package: &Package, package_config: &ProgramMetadata) -> bool {
+    package_config.program.unwrap_or_else(|| {
+        package.dependencies.iter().any(|dependency| {
+            dependency.kind == DependencyKind::Normal
+                && SOLANA_PROGRAM_CRATES.contains(&dependency.name.as_str())
+        })
+    })
 }
 
-/// Resolve target name using overrides
+/// Resolve target name using consumer overrides, then the program's own metadata
 fn resolve_target_name(
     base_target_name: &str,
     manifest_path: &Path,
-    targets_overrides: &HashMap<PathBuf, String>,
+    targets_overrides: &HashMap<OverrideKey, String>,
+    package_target: Option<&str>,
 ) -> String {
-    targets_overrides
-        .get(manifest_path)
+    lookup_override(targets_overrides, manifest_path, base_target_name)
         .cloned()
+        .or_else(|| package_target.map(str::to_string))
         .unwrap_or_else(|| base_target_name.to_string())
 }
 
-/// Resolve constant name using overrides
+/// Resolve constant name using consumer overrides, then the program's own metadata
 fn resolve_constant_name(
     base_target_name: &str,
     manifest_path: &Path,
-    constants_overrides: &HashMap<PathBuf, String>,
+    constants_overrides: &HashMap<OverrideKey, String>,
+    package_constant: Option<&str>,
 ) -> String {
-    constants_overrides
-        .get(manifest_path)
+    lookup_override(constants_overrides, manifest_path, base_target_name)
         .cloned()
+        .or_else(|| package_constant.map(str::to_string))
         .unwrap_or_else(|| format!("{}_ELF", base_target_name.to_uppercase()))
 }
 
-/// Check if a program should be included in laser-eyes mode (matches only patterns)
-fn should_only_include_program(program: &SolanaProgram, only_patterns: &[String]) -> bool {
-    only_patterns
-        .iter()
-        .any(|pattern| matches_program_pattern(program, pattern))
+/// Resolve program ID from overrides, falling back to the `declare_id!` in the target's source
+fn resolve_program_id(
+    base_target_name: &str,
+    manifest_path: &Path,
+    program_ids_overrides: &HashMap<OverrideKey, String>,
+    src_path: &Path,
+) -> Option<String> {
+    lookup_override(program_ids_overrides, manifest_path, base_target_name)
+        .cloned()
+        .or_else(|| {
+            let source = fs::read_to_string(src_path).ok()?;
+            find_declared_id(&source)
+        })
 }
 
-/// Check if a program should be included (not denied by glob patterns)
-fn should_include_program_permissive(program: &SolanaProgram, deny_patterns: &[String]) -> bool {
-    !deny_patterns
-        .iter()
-        .any(|pattern| matches_program_pattern(program, pattern))
+/// Find the program ID the `declare_id!("...")`s in a source file agree on
+///
+/// Several different IDs are usually picked between by `#[cfg]`s, which this can't evaluate,
+/// so that case yields no ID rather than a guess; a `program_ids` entry settles it.
+fn find_declared_id(source: &str) -> Option<String> {
+    let mut declared = source
+        .match_indices("declare_id!")
+        .filter_map(|(start, _)| {
+            let args = source[start..].split_once('(')?.1;
+            let literal = args.trim_start().strip_prefix('"')?;
+            let program_id = literal.split_once('"')?.0;
+            decode_program_id(program_id).map(|_| program_id)
+        });
+
+    let program_id = declared.next()?;
+    declared
+        .all(|other| other == program_id)
+        .then(|| program_id.to_string())
 }
 
-fn matches_program_pattern(program: &SolanaProgram, pattern: &str) -> bool {
-    if let Some(target_pattern) = pattern.strip_prefix("target:") {
-        matches_glob(&program.target_name, target_pattern)
-    } else if let Some(package_pattern) = pattern.strip_prefix("package:") {
-        matches_glob(&program.package_name, package_pattern)
-    } else if let Some(path_pattern) = pattern.strip_prefix("path:") {
-        matches_glob(&program.manifest_path.to_string_lossy(), path_pattern)
-    } else {
-        // No fallback - invalid pattern
-        eprintln!(
-            "Warning: Invalid deny pattern '{}'. Use 'target:', 'package:', or 'path:' prefix.",
-            pattern
-        );
-        false
+fn matches_filter_pattern(program: &SolanaProgram, pattern: &FilterPattern) -> bool {
+    match pattern {
+        FilterPattern::Matcher(matcher) => matches_program_pattern(program, matcher),
+        FilterPattern::All { all } => all.iter().all(|p| matches_filter_pattern(program, p)),
+        FilterPattern::Any { any } => any.iter().any(|p| matches_filter_pattern(program, p)),
     }
 }
 
-fn matches_glob(text: &str, pattern: &str) -> bool {
-    glob::Pattern::new(pattern)
-        .map(|p| p.matches(text))
-        .unwrap_or(false)
+fn matches_program_pattern(program: &SolanaProgram, matcher: &Matcher) -> bool {
+    let matched = match matcher.field {
+        MatchField::Target => matcher.value.matches(&program.target_name),
+        MatchField::Package => matcher.value.matches(&program.package_name),
+        MatchField::Path => matcher
+            .value
+            .matches(&program.manifest_path.to_string_lossy()),
+        MatchField::Constant => matcher.value.matches(&program.constant_name),
+        MatchField::Tag => program.tags.iter().any(|tag| matcher.value.matches(tag)),
+    };
+
+    matched != matcher.negated
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::MatchValue;
     use std::path::PathBuf;
 
+    fn pattern(source: &str) -> FilterPattern {
+        source.parse().unwrap()
+    }
+
+    fn should_only_include_program(
+        program: &SolanaProgram,
+        only_patterns: &[FilterPattern],
+    ) -> bool {
+        let filter_mode = FilterMode::Only(with_origin(only_patterns, PatternOrigin::Only));
+        filter_mode.apply(program).0.is_included()
+    }
+
+    fn should_include_program_permissive(
+        program: &SolanaProgram,
+        deny_patterns: &[FilterPattern],
+    ) -> bool {
+        let filter_mode = FilterMode::Deny(with_origin(deny_patterns, PatternOrigin::Deny));
+        filter_mode.apply(program).0.is_included()
+    }
+
+    fn matches(program: &SolanaProgram, source: &str) -> bool {
+        matches_filter_pattern(program, &pattern(source))
+    }
+
+    fn matches_glob(text: &str, pattern: &str) -> bool {
+        pattern.parse::<MatchValue>().unwrap().matches(text)
+    }
+
     fn sample_program(target_name: &str, package_name: &str) -> SolanaProgram {
         SolanaProgram {
             target_name: target_name.to_string(),
+            build_target: BuildTarget::Lib(target_name.to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             package_name: package_name.to_string(),
             manifest_path: PathBuf::from("/workspace/Cargo.toml"),
             constant_name: format!("{}_ELF", target_name.to_uppercase()),
@@ -267,7 +1025,7 @@ mod tests {
     #[test]
     fn test_should_include_program_target_exclusion_match() {
         let program = sample_program("test_program", "my_package");
-        let deny_patterns = vec!["target:test*".to_string()];
+        let deny_patterns = vec![pattern("target:test*")];
 
         assert!(!should_include_program_permissive(&program, &deny_patterns));
     }
@@ -275,7 +1033,7 @@ mod tests {
     #[test]
     fn test_should_include_program_target_exclusion_no_match() {
         let program = sample_program("main_program", "my_package");
-        let deny_patterns = vec!["target:test*".to_string()];
+        let deny_patterns = vec![pattern("target:test*")];
 
         assert!(should_include_program_permissive(&program, &deny_patterns));
     }
@@ -283,7 +1041,7 @@ mod tests {
     #[test]
     fn test_should_include_program_package_exclusion_match() {
         let program = sample_program("my_target", "dev_package");
-        let deny_patterns = vec!["package:dev*".to_string()];
+        let deny_patterns = vec![pattern("package:dev*")];
 
         assert!(!should_include_program_permissive(&program, &deny_patterns));
     }
@@ -291,7 +1049,7 @@ mod tests {
     #[test]
     fn test_should_include_program_package_exclusion_no_match() {
         let program = sample_program("my_target", "main_package");
-        let deny_patterns = vec!["package:dev*".to_string()];
+        let deny_patterns = vec![pattern("package:dev*")];
 
         assert!(should_include_program_permissive(&program, &deny_patterns));
     }
@@ -300,11 +1058,17 @@ mod tests {
     fn test_should_include_program_path_exclusion_match() {
         let program = SolanaProgram {
             target_name: "my_target".to_string(),
+            build_target: BuildTarget::Lib("my_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             package_name: "my_package".to_string(),
             manifest_path: PathBuf::from("/workspace/examples/test/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
-        let deny_patterns = vec!["path:*/examples/*".to_string()];
+        let deny_patterns = vec![pattern("path:*/examples/*")];
 
         assert!(!should_include_program_permissive(&program, &deny_patterns));
     }
@@ -313,11 +1077,17 @@ mod tests {
     fn test_should_include_program_path_exclusion_no_match() {
         let program = SolanaProgram {
             target_name: "my_target".to_string(),
+            build_target: BuildTarget::Lib("my_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             package_name: "my_package".to_string(),
             manifest_path: PathBuf::from("/workspace/src/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
-        let deny_patterns = vec!["path:*/examples/*".to_string()];
+        let deny_patterns = vec![pattern("path:*/examples/*")];
 
         assert!(should_include_program_permissive(&program, &deny_patterns));
     }
@@ -325,7 +1095,7 @@ mod tests {
     #[test]
     fn test_should_include_program_multiple_exclusions() {
         let program = sample_program("test_target", "dev_package");
-        let deny_patterns = vec!["target:test*".to_string(), "package:dev*".to_string()];
+        let deny_patterns = vec![pattern("target:test*"), pattern("package:dev*")];
 
         // Should be denied because it matches the target pattern
         assert!(!should_include_program_permissive(&program, &deny_patterns));
@@ -334,7 +1104,7 @@ mod tests {
     #[test]
     fn test_should_include_program_multiple_exclusions_no_match() {
         let program = sample_program("main_target", "main_package");
-        let deny_patterns = vec!["target:test*".to_string(), "package:dev*".to_string()];
+        let deny_patterns = vec![pattern("target:test*"), pattern("package:dev*")];
 
         assert!(should_include_program_permissive(&program, &deny_patterns));
     }
@@ -343,42 +1113,46 @@ mod tests {
     fn test_matches_program_pattern_target() {
         let program = sample_program("test_program", "my_package");
 
-        assert!(matches_program_pattern(&program, "target:test*"));
-        assert!(matches_program_pattern(&program, "target:test_program"));
-        assert!(!matches_program_pattern(&program, "target:main*"));
+        assert!(matches(&program, "target:test*"));
+        assert!(matches(&program, "target:test_program"));
+        assert!(!matches(&program, "target:main*"));
     }
 
     #[test]
     fn test_matches_program_pattern_package() {
         let program = sample_program("my_target", "dev_package");
 
-        assert!(matches_program_pattern(&program, "package:dev*"));
-        assert!(matches_program_pattern(&program, "package:dev_package"));
-        assert!(!matches_program_pattern(&program, "package:main*"));
+        assert!(matches(&program, "package:dev*"));
+        assert!(matches(&program, "package:dev_package"));
+        assert!(!matches(&program, "package:main*"));
     }
 
     #[test]
     fn test_matches_program_pattern_path() {
         let program = SolanaProgram {
             target_name: "my_target".to_string(),
+            build_target: BuildTarget::Lib("my_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             package_name: "my_package".to_string(),
             manifest_path: PathBuf::from("/workspace/examples/basic/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
 
-        assert!(matches_program_pattern(&program, "path:*/examples/*"));
-        assert!(matches_program_pattern(&program, "path:*/basic/*"));
-        assert!(!matches_program_pattern(&program, "path:*/src/*"));
+        assert!(matches(&program, "path:*/examples/*"));
+        assert!(matches(&program, "path:*/basic/*"));
+        assert!(!matches(&program, "path:*/src/*"));
     }
 
     #[test]
     fn test_matches_program_pattern_invalid_prefix() {
-        let program = sample_program("test_program", "my_package");
-
-        // Invalid patterns should return false and print warning
-        assert!(!matches_program_pattern(&program, "invalid:test*"));
-        assert!(!matches_program_pattern(&program, "test*")); // No prefix
-        assert!(!matches_program_pattern(&program, "random_pattern"));
+        // Invalid patterns are rejected when parsed instead of silently never matching
+        assert!("invalid:test*".parse::<FilterPattern>().is_err());
+        assert!("test*".parse::<FilterPattern>().is_err()); // No prefix
+        assert!("random_pattern".parse::<FilterPattern>().is_err());
     }
 
     #[test]
@@ -409,8 +1183,8 @@ mod tests {
 
     #[test]
     fn test_matches_glob_invalid_pattern() {
-        // Invalid glob pattern should not match anything
-        assert!(!matches_glob("test", "[invalid"));
+        // Invalid globs are rejected up front instead of silently matching nothing
+        assert!("[invalid".parse::<MatchValue>().is_err());
     }
 
     #[test]
@@ -420,8 +1194,8 @@ mod tests {
         let program3 = sample_program("my_target", "dev-package");
 
         // Test merging: global excludes + workspace excludes
-        let global_denies = vec!["package:apl-*".to_string()];
-        let workspace_denies = vec!["target:test*".to_string()];
+        let global_denies = vec![pattern("package:apl-*")];
+        let workspace_denies = vec![pattern("target:test*")];
         let mut merged_denies = global_denies.clone();
         merged_denies.extend(workspace_denies);
 
@@ -444,7 +1218,7 @@ mod tests {
     #[test]
     fn test_should_only_include_program_target_match() {
         let program = sample_program("token_manager", "my_package");
-        let only_patterns = vec!["target:token*".to_string()];
+        let only_patterns = vec![pattern("target:token*")];
 
         assert!(should_only_include_program(&program, &only_patterns));
     }
@@ -452,7 +1226,7 @@ mod tests {
     #[test]
     fn test_should_only_include_program_target_no_match() {
         let program = sample_program("governance", "my_package");
-        let only_patterns = vec!["target:token*".to_string()];
+        let only_patterns = vec![pattern("target:token*")];
 
         assert!(!should_only_include_program(&program, &only_patterns));
     }
@@ -460,7 +1234,7 @@ mod tests {
     #[test]
     fn test_should_only_include_program_package_match() {
         let program = sample_program("my_target", "token_program");
-        let only_patterns = vec!["package:token*".to_string()];
+        let only_patterns = vec![pattern("package:token*")];
 
         assert!(should_only_include_program(&program, &only_patterns));
     }
@@ -468,7 +1242,7 @@ mod tests {
     #[test]
     fn test_should_only_include_program_package_no_match() {
         let program = sample_program("my_target", "governance_program");
-        let only_patterns = vec!["package:token*".to_string()];
+        let only_patterns = vec![pattern("package:token*")];
 
         assert!(!should_only_include_program(&program, &only_patterns));
     }
@@ -479,7 +1253,8 @@ mod tests {
         let program2 = sample_program("governance", "my_package");
         let program3 = sample_program("other_program", "my_package");
 
-        let only_patterns = vec!["target:token*".to_string(), "target:governance".to_string()];
+        let only_patterns: Vec<FilterPattern> =
+            vec![pattern("target:token*"), pattern("target:governance")];
 
         // Should match both token* and governance patterns
         assert!(should_only_include_program(&program1, &only_patterns));
@@ -502,15 +1277,129 @@ mod tests {
     fn test_should_only_include_program_path_match() {
         let program = SolanaProgram {
             target_name: "my_target".to_string(),
+            build_target: BuildTarget::Lib("my_target".to_string()),
+            workspace_alias: None,
+            program_id: None,
+            build: Default::default(),
+            tags: vec![],
+            decision: Default::default(),
             package_name: "my_package".to_string(),
             manifest_path: PathBuf::from("/workspace/programs/core/Cargo.toml"),
             constant_name: "MY_TARGET_ELF".to_string(),
         };
-        let only_patterns = vec!["path:*/programs/core/*".to_string()];
+        let only_patterns = vec![pattern("path:*/programs/core/*")];
 
         assert!(should_only_include_program(&program, &only_patterns));
     }
 
+    #[test]
+    fn test_negated_constant_and_tag_patterns() {
+        let mut program = sample_program("vault", "vault_program");
+        program.tags = vec!["core".to_string(), "audited".to_string()];
+
+        assert!(matches(&program, "constant:VAULT_*"));
+        assert!(matches(&program, "tag:core"));
+        assert!(matches(&program, "tag:aud*"));
+        assert!(!matches(&program, "tag:test"));
+        assert!(matches(&program, "!tag:test"));
+        assert!(!matches(&program, "!target:vault"));
+    }
+
+    #[test]
+    fn test_regex_patterns() {
+        let program = sample_program("vault_v2", "vault_program");
+
+        assert!(matches(&program, r"target:regex:^vault_v\d+$"));
+        assert!(!matches(&program, "target:regex:^escrow"));
+        assert!(matches(&program, "!package:regex:-test$"));
+    }
+
+    #[test]
+    fn test_all_of_and_any_of_patterns() {
+        let mut program = sample_program("vault", "vault_program");
+        program.manifest_path = PathBuf::from("/workspace/programs/vault/Cargo.toml");
+        let mut test_program = program.clone();
+        test_program.target_name = "test_vault".to_string();
+
+        // All programs under programs/ except the test ones
+        let only = vec![FilterPattern::All {
+            all: vec![pattern("path:*/programs/*"), pattern("!target:test_*")],
+        }];
+        assert!(should_only_include_program(&program, &only));
+        assert!(!should_only_include_program(&test_program, &only));
+
+        let deny = vec![FilterPattern::Any {
+            any: vec![pattern("target:test_*"), pattern("package:*-mock")],
+        }];
+        assert!(should_include_program_permissive(&program, &deny));
+        assert!(!should_include_program_permissive(&test_program, &deny));
+    }
+
+    #[test]
+    fn test_resolve_workspace_alias_defaults_to_directory_name() {
+        let config_file_dir = Path::new("/repo/elves");
+        let alias = |manifest_path| resolve_workspace_alias(None, config_file_dir, manifest_path);
+
+        assert_eq!(alias("examples/basic/Cargo.toml").unwrap(), "basic");
+        assert_eq!(
+            alias("../Token-Programs/Cargo.toml").unwrap(),
+            "token_programs"
+        );
+        assert_eq!(alias("examples/2024/Cargo.toml").unwrap(), "ws_2024");
+        assert_eq!(alias("core/Cargo.toml").unwrap(), "core_workspace");
+
+        // Named after the directory the path resolves to, not how it's spelled
+        assert_eq!(alias("./Cargo.toml").unwrap(), "elves");
+        assert_eq!(alias("Cargo.toml").unwrap(), "elves");
+        assert_eq!(alias("../Cargo.toml").unwrap(), "repo");
+        assert_eq!(
+            alias("../programs/../examples/basic/Cargo.toml").unwrap(),
+            alias("/repo/examples/basic/Cargo.toml").unwrap()
+        );
+        assert_eq!(
+            resolve_workspace_alias(None, Path::new("/"), "Cargo.toml").unwrap(),
+            "root"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_resolve_workspace_alias_follows_symlinks() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let workspace_dir = temp_dir.path().join("programs");
+        fs::create_dir_all(&workspace_dir).unwrap();
+        fs::write(workspace_dir.join("Cargo.toml"), "[workspace]\n").unwrap();
+        std::os::unix::fs::symlink(&workspace_dir, temp_dir.path().join("linked")).unwrap();
+
+        let alias = resolve_workspace_alias(None, temp_dir.path(), "linked/Cargo.toml").unwrap();
+        assert_eq!(alias, "programs");
+    }
+
+    #[test]
+    fn test_resolve_workspace_alias_explicit() {
+        let config_file_dir = Path::new("/repo/elves");
+        let alias = |alias, manifest_path| {
+            resolve_workspace_alias(Some(alias), config_file_dir, manifest_path)
+        };
+
+        assert_eq!(alias("programs", "./Cargo.toml").unwrap(), "programs");
+
+        let error = alias("My-Examples", "examples/Cargo.toml")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Invalid workspace alias 'My-Examples'"));
+        assert!(error.contains("'my_examples'"));
+
+        // Would shadow the `core` crate inside the generated file
+        let error = alias("core", "./Cargo.toml").unwrap_err().to_string();
+        assert!(error.contains("Invalid workspace alias 'core'"));
+        assert!(error.contains("'core_workspace'"));
+
+        // Would clash with the generated `harness` module
+        let error = alias("harness", "./Cargo.toml").unwrap_err().to_string();
+        assert!(error.contains("'harness_workspace'"));
+    }
+
     #[test]
     fn test_filter_mode_magic() {
         let filter_mode = FilterMode::Magic;
@@ -525,11 +1414,11 @@ mod tests {
 
     #[test]
     fn test_filter_mode_exclude() {
-        let filter_mode = FilterMode::Deny(vec!["target:test*".to_string()]);
+        let filter_mode = FilterMode::Deny(vec![(pattern("target:test*"), PatternOrigin::Deny)]);
 
         match filter_mode {
             FilterMode::Deny(patterns) => {
-                assert_eq!(patterns, vec!["target:test*"]);
+                assert_eq!(patterns[0].0, "target:test*");
             }
             _ => panic!("Expected Exclude filter mode"),
         }
@@ -537,13 +1426,717 @@ mod tests {
 
     #[test]
     fn test_filter_mode_include() {
-        let filter_mode = FilterMode::Only(vec!["target:token*".to_string()]);
+        let filter_mode = FilterMode::Only(vec![(pattern("target:token*"), PatternOrigin::Only)]);
 
         match filter_mode {
             FilterMode::Only(patterns) => {
-                assert_eq!(patterns, vec!["target:token*"]);
+                assert_eq!(patterns[0].0, "target:token*");
             }
             _ => panic!("Expected Include filter mode"),
         }
     }
+
+    #[test]
+    fn test_find_declared_id() {
+        let source = r#"
+use solana_program::declare_id;
+
+declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+"#;
+        assert_eq!(
+            find_declared_id(source).as_deref(),
+            Some("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
+        );
+
+        // Macro paths, whitespace, and non-literal arguments before the real one
+        let source = r#"
+// declare_id!(ID) in docs doesn't count
+pinocchio_pubkey::declare_id!( "11111111111111111111111111111111" );
+"#;
+        assert_eq!(
+            find_declared_id(source).as_deref(),
+            Some("11111111111111111111111111111111")
+        );
+
+        assert_eq!(find_declared_id("declare_id!(\"not-a-key\");"), None);
+        assert_eq!(find_declared_id("fn main() {}"), None);
+
+        // IDs picked by cfg can't be told apart, so neither is chosen
+        let source = r#"
+#[cfg(feature = "devnet")]
+declare_id!("11111111111111111111111111111111");
+#[cfg(not(feature = "devnet"))]
+declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+"#;
+        assert_eq!(find_declared_id(source), None);
+
+        // The same ID declared twice is still that ID
+        let source = r#"
+#[cfg(feature = "devnet")]
+declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+#[cfg(not(feature = "devnet"))]
+declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+"#;
+        assert_eq!(
+            find_declared_id(source).as_deref(),
+            Some("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
+        );
+    }
+
+    #[test]
+    fn test_resolve_program_id_prefers_override() {
+        let manifest_path = Path::new("/workspace/vault/Cargo.toml");
+        let overrides = HashMap::from([(
+            OverrideKey {
+                manifest_path: manifest_path.to_path_buf(),
+                target: None,
+            },
+            "11111111111111111111111111111111".to_string(),
+        )]);
+
+        assert_eq!(
+            resolve_program_id(
+                "vault",
+                manifest_path,
+                &overrides,
+                Path::new("/nonexistent.rs")
+            )
+            .as_deref(),
+            Some("11111111111111111111111111111111")
+        );
+        assert_eq!(
+            resolve_program_id(
+                "vault",
+                manifest_path,
+                &HashMap::new(),
+                Path::new("/nonexistent.rs")
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_program_ids_rejects_invalid_keys() {
+        let program_ids = HashMap::from([(
+            OverrideKey {
+                manifest_path: PathBuf::from("/workspace/vault/Cargo.toml"),
+                target: None,
+            },
+            "not-a-key".to_string(),
+        )]);
+
+        let error = check_program_ids(&program_ids).unwrap_err().to_string();
+        assert!(error.contains("Invalid program ID 'not-a-key'"));
+    }
+
+    /// Write a throwaway workspace of cdylib members and load it in Magic mode
+    fn temp_workspace(members: &[(&str, &str)]) -> (tempfile::TempDir, Workspace) {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let member_names = members
+            .iter()
+            .map(|(name, _)| format!("\"{}\"", name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        std::fs::write(
+            root.join("Cargo.toml"),
+            format!(
+                "[workspace]\nmembers = [{}]\nresolver = \"2\"\n",
+                member_names
+            ),
+        )
+        .unwrap();
+
+        for (name, extra) in members {
+            let dir = root.join(name);
+            std::fs::create_dir_all(dir.join("src")).unwrap();
+            std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+            std::fs::write(
+                dir.join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\ncrate-type = [\"cdylib\"]\n\n{}",
+                    name, extra
+                ),
+            )
+            .unwrap();
+        }
+
+        let metadata = MetadataCommand::new()
+            .manifest_path(root.join("Cargo.toml"))
+            .no_deps()
+            .exec()
+            .unwrap();
+
+        let workspace = Workspace {
+            metadata,
+            manifest_path: root.join("Cargo.toml").display().to_string(),
+            alias: None,
+            filter_mode: FilterMode::Magic,
+            constants_overrides: HashMap::new(),
+            targets_overrides: HashMap::new(),
+            program_ids_overrides: HashMap::new(),
+            build: BuildSettings::default(),
+            elves_crate_dir: PathBuf::from("/nonexistent/elves"),
+        };
+
+        (temp_dir, workspace)
+    }
+
+    fn names<'a>(programs: impl IntoIterator<Item = &'a SolanaProgram>) -> Vec<String> {
+        programs
+            .into_iter()
+            .map(|p| p.target_name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_discover_programs_skips_non_solana_cdylibs() {
+        let (_temp_dir, workspace) = temp_workspace(&[
+            ("vault", "[dependencies]\npinocchio = \"0.8\"\n"),
+            ("wasm", "[dependencies]\nwasm-bindgen = \"0.2\"\n\n[dev-dependencies]\nsolana-program = \"2\"\n"),
+            ("ffi", "[package.metadata.elf-magic]\nprogram = true\n"),
+        ]);
+
+        let discovered = workspace.discover_programs().unwrap();
+
+        // Dev-dependencies don't make a program; the marker overrides detection
+        assert_eq!(names(&discovered.included), vec!["ffi", "vault"]);
+        assert_eq!(
+            discovered
+                .skipped
+                .iter()
+                .map(|(p, reason)| (p.target_name.as_str(), *reason))
+                .collect::<Vec<_>>(),
+            vec![("wasm", SkipReason::NotAProgram)]
+        );
+        assert!(discovered.excluded.is_empty());
+    }
+
+    #[test]
+    fn test_filter_mode_only_then_deny() {
+        let filter_mode = FilterMode::from_lists(
+            with_origin(&[pattern("path:*/programs/*")], PatternOrigin::GlobalOnly),
+            Some(with_origin(
+                &[pattern("target:vault*")],
+                PatternOrigin::Only,
+            )),
+            merge_patterns(&[], &[pattern("target:*_test")], PatternOrigin::Deny),
+        );
+        assert!(matches!(filter_mode, FilterMode::OnlyThenDeny(_)));
+
+        let mut program = sample_program("vault", "vault");
+        program.manifest_path = PathBuf::from("/repo/programs/vault/Cargo.toml");
+        let (decision, matched) = filter_mode.apply(&program);
+        assert_eq!(
+            decision.to_string(),
+            "matched path:*/programs/* from global_only"
+        );
+        assert_eq!(matched, vec![true, true, false]);
+
+        // Deny applies to what only let through
+        program.target_name = "vault_test".to_string();
+        let (decision, _) = filter_mode.apply(&program);
+        assert_eq!(decision.to_string(), "denied by target:*_test from deny");
+
+        // Only narrows first, so a program neither list names stays out, and the deny
+        // pattern it matches doesn't count: it changed nothing
+        let mut program = sample_program("escrow_test", "escrow");
+        program.manifest_path = PathBuf::from("/repo/examples/escrow/Cargo.toml");
+        let (decision, matched) = filter_mode.apply(&program);
+        assert_eq!(decision, Decision::by_rule(FilterRule::NotInOnly));
+        assert_eq!(matched, vec![false, false, false]);
+
+        // Either list alone keeps its own mode
+        let only = with_origin(&[pattern("target:vault")], PatternOrigin::Only);
+        assert!(matches!(
+            FilterMode::from_lists(vec![], Some(only), vec![]),
+            FilterMode::Only(_)
+        ));
+        assert!(matches!(
+            FilterMode::from_lists(vec![], None, vec![]),
+            FilterMode::Deny(_)
+        ));
+    }
+
+    #[test]
+    fn test_filter_mode_global_only_narrows_separately() {
+        let filter_mode = FilterMode::from_lists(
+            with_origin(&[pattern("path:*/programs/*")], PatternOrigin::GlobalOnly),
+            Some(with_origin(
+                &[pattern("target:vault*")],
+                PatternOrigin::Only,
+            )),
+            vec![],
+        );
+
+        // Matching the workspace's `only` isn't enough outside `global_only`
+        let mut program = sample_program("vault", "vault");
+        program.manifest_path = PathBuf::from("/repo/examples/vault/Cargo.toml");
+        let (decision, matched) = filter_mode.apply(&program);
+        assert_eq!(decision, Decision::by_rule(FilterRule::NotInOnly));
+        assert_eq!(matched, vec![false, true]);
+
+        // Nor is matching `global_only` outside the workspace's `only`
+        let mut program = sample_program("escrow", "escrow");
+        program.manifest_path = PathBuf::from("/repo/programs/escrow/Cargo.toml");
+        let (decision, matched) = filter_mode.apply(&program);
+        assert_eq!(decision, Decision::by_rule(FilterRule::NotInOnly));
+        assert_eq!(matched, vec![true, false]);
+
+        // Without a workspace `only`, `global_only` narrows by itself
+        let filter_mode = FilterMode::from_lists(
+            with_origin(&[pattern("path:*/programs/*")], PatternOrigin::GlobalOnly),
+            None,
+            vec![],
+        );
+        let (decision, _) = filter_mode.apply(&program);
+        assert_eq!(
+            decision.to_string(),
+            "matched path:*/programs/* from global_only"
+        );
+        program.manifest_path = PathBuf::from("/repo/examples/escrow/Cargo.toml");
+        let (decision, _) = filter_mode.apply(&program);
+        assert_eq!(decision, Decision::by_rule(FilterRule::NotInOnly));
+    }
+
+    #[test]
+    fn test_filter_mode_apply_records_decision() {
+        let program = sample_program("test_vault", "apl-vault");
+        let filter_mode = FilterMode::Deny(vec![
+            (pattern("target:bench*"), PatternOrigin::GlobalDeny),
+            (pattern("package:apl-*"), PatternOrigin::GlobalDeny),
+            (pattern("target:test*"), PatternOrigin::Deny),
+        ]);
+
+        let (decision, matched) = filter_mode.apply(&program);
+        assert!(!decision.is_included());
+        assert_eq!(
+            decision.to_string(),
+            "denied by package:apl-* from global_deny"
+        );
+        assert_eq!(matched, vec![false, true, true]);
+
+        let (decision, _) = FilterMode::Deny(vec![]).apply(&program);
+        assert_eq!(decision, Decision::by_rule(FilterRule::NotDenied));
+
+        let (decision, _) = FilterMode::Only(vec![]).apply(&program);
+        assert_eq!(decision, Decision::by_rule(FilterRule::NotInOnly));
+        assert!(!decision.is_included());
+
+        let (decision, matched) = FilterMode::Magic.apply(&program);
+        assert_eq!(decision, Decision::by_rule(FilterRule::IncludeAll));
+        assert!(matched.is_empty());
+    }
+
+    #[test]
+    fn test_discover_programs_counts_pattern_matches() {
+        let (_temp_dir, mut workspace) = temp_workspace(&[
+            ("vault", "[dependencies]\npinocchio = \"0.8\"\n"),
+            ("escrow", "[dependencies]\npinocchio = \"0.8\"\n"),
+        ]);
+        workspace.filter_mode = FilterMode::Only(vec![
+            (pattern("target:*"), PatternOrigin::Only),
+            (pattern("target:vault"), PatternOrigin::Only),
+            (pattern("target:old_vault"), PatternOrigin::Only),
+        ]);
+
+        let discovered = workspace.discover_programs().unwrap();
+
+        assert_eq!(names(&discovered.included), vec!["escrow", "vault"]);
+        // The first matching pattern decides
+        assert_eq!(
+            discovered.included[1].decision,
+            Decision::by_pattern(
+                FilterRule::OnlyMatched,
+                "target:*".to_string(),
+                PatternOrigin::Only
+            )
+        );
+        assert_eq!(
+            discovered
+                .pattern_usage
+                .iter()
+                .map(|usage| (usage.pattern.as_str(), usage.matches))
+                .collect::<Vec<_>>(),
+            vec![
+                ("target:*", 2),
+                ("target:vault", 1),
+                ("target:old_vault", 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_deny_pattern_behind_only_is_unused() {
+        let (_temp_dir, mut workspace) = temp_workspace(&[
+            ("vault", "[dependencies]\npinocchio = \"0.8\"\n"),
+            ("escrow", "[dependencies]\npinocchio = \"0.8\"\n"),
+        ]);
+        workspace.filter_mode = FilterMode::from_lists(
+            vec![],
+            Some(with_origin(&[pattern("target:vault")], PatternOrigin::Only)),
+            with_origin(&[pattern("target:escrow")], PatternOrigin::Deny),
+        );
+
+        // `only` already left escrow out, so denying it changes nothing
+        let discovered = workspace.discover_programs().unwrap();
+        assert_eq!(names(&discovered.included), vec!["vault"]);
+        assert_eq!(
+            discovered.excluded[0].decision,
+            Decision::by_rule(FilterRule::NotInOnly)
+        );
+        assert_eq!(
+            crate::programs::find_unused_patterns(&[discovered]),
+            vec![crate::programs::UnusedPattern {
+                pattern: "target:escrow".to_string(),
+                origin: PatternOrigin::Deny,
+                workspace_path: Some(workspace.manifest_path.clone()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_discover_programs_applies_workspace_build_settings() {
+        let (_temp_dir, mut workspace) = temp_workspace(&[
+            (
+                "vault",
+                "[package.metadata.elf-magic]\nprogram = true\nfeatures = [\"no-log\"]\ntools_version = \"v1.44\"\nenv = { RUSTFLAGS = \"-C opt-level=z\" }\n",
+            ),
+            ("escrow", "[package.metadata.elf-magic]\nprogram = true\n"),
+        ]);
+        workspace.build = BuildSettings {
+            features: vec!["devnet".to_string()],
+            extra_args: vec!["--arch".to_string(), "v3".to_string()],
+            env: [("RUSTFLAGS", "-C opt-level=3"), ("SBF_OUT", "examples")]
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .into(),
+            tools_version: Some("v1.43".to_string()),
+            target_dir: Some(PathBuf::from("/tmp/examples-target")),
+            ..Default::default()
+        };
+
+        let discovered = workspace.discover_programs().unwrap();
+        assert_eq!(names(&discovered.included), vec!["escrow", "vault"]);
+
+        // Without program-side settings, the workspace's apply as-is
+        assert_eq!(discovered.included[0].build, workspace.build);
+
+        // Program-side settings win
+        let vault = &discovered.included[1].build;
+        assert_eq!(vault.features, vec!["no-log"]);
+        assert_eq!(vault.tools_version.as_deref(), Some("v1.44"));
+        assert_eq!(vault.env["RUSTFLAGS"], "-C opt-level=z");
+        assert_eq!(vault.env["SBF_OUT"], "examples");
+        assert_eq!(vault.extra_args, vec!["--arch", "v3"]);
+        assert_eq!(vault.target_dir, workspace.build.target_dir);
+    }
+
+    #[test]
+    fn test_discover_programs_skips_elves_crate_and_dependents() {
+        let (temp_dir, mut workspace) = temp_workspace(&[
+            // The consumer's own table is config, not program-side metadata
+            ("elves", "[package.metadata.elf-magic]\nmode = \"magic\"\n"),
+            (
+                "harness",
+                "[package.metadata.elf-magic]\nprogram = true\n\n[dependencies]\nelves = { path = \"../elves\" }\n",
+            ),
+            (
+                "embedder",
+                "[package.metadata.elf-magic]\nprogram = true\n\n[build-dependencies]\nharness = { path = \"../harness\" }\n",
+            ),
+            (
+                "vault",
+                "[package.metadata.elf-magic]\nprogram = true\n\n[dev-dependencies]\nelves = { path = \"../elves\" }\n",
+            ),
+        ]);
+        workspace.elves_crate_dir = std::fs::canonicalize(temp_dir.path().join("elves")).unwrap();
+
+        let discovered = workspace.discover_programs().unwrap();
+
+        // Dev-dependencies never get built by build-sbf, so they don't count
+        assert_eq!(names(&discovered.included), vec!["vault"]);
+        let skipped: Vec<(String, SkipReason)> = discovered
+            .skipped
+            .iter()
+            .map(|(program, reason)| (program.target_name.clone(), *reason))
+            .collect();
+        assert_eq!(
+            skipped,
+            vec![
+                ("elves".to_string(), SkipReason::ElvesCrate),
+                ("embedder".to_string(), SkipReason::DependsOnElvesCrate),
+                ("harness".to_string(), SkipReason::DependsOnElvesCrate),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discover_programs_reads_program_side_metadata() {
+        let (_temp_dir, mut workspace) = temp_workspace(&[
+            (
+                "vault",
+                "[package.metadata.elf-magic]\nprogram = true\nconstant = \"VAULT_PROGRAM_ELF\"\ntarget = \"vault_program\"\nfeatures = [\"no-log\"]\ntags = [\"core\"]\n",
+            ),
+            (
+                "escrow",
+                "[package.metadata.elf-magic]\nprogram = true\nconstant = \"ESCROW_PROGRAM_ELF\"\n",
+            ),
+            (
+                "fixture",
+                "[package.metadata.elf-magic]\nprogram = true\nskip = true\n",
+            ),
+        ]);
+
+        // The consumer's own overrides win over what the program declares
+        let escrow_manifest = workspace
+            .metadata
+            .packages
+            .iter()
+            .find(|p| p.name.as_str() == "escrow")
+            .unwrap()
+            .manifest_path
+            .as_std_path()
+            .to_path_buf();
+        workspace.constants_overrides.insert(
+            OverrideKey {
+                manifest_path: escrow_manifest,
+                target: None,
+            },
+            "ESCROW_ELF_V2".to_string(),
+        );
+
+        let discovered = workspace.discover_programs().unwrap();
+
+        assert_eq!(names(&discovered.included), vec!["escrow", "vault_program"]);
+        let vault = &discovered.included[1];
+        assert_eq!(vault.constant_name, "VAULT_PROGRAM_ELF");
+        assert_eq!(vault.build.features, vec!["no-log"]);
+        assert_eq!(vault.tags, vec!["core"]);
+        assert_eq!(vault.build_target, BuildTarget::Lib("vault".to_string()));
+        assert_eq!(discovered.included[0].constant_name, "ESCROW_ELF_V2");
+
+        assert_eq!(discovered.skipped.len(), 1);
+        assert_eq!(discovered.skipped[0].0.target_name, "fixture");
+        assert_eq!(discovered.skipped[0].1, SkipReason::SkippedByPackage);
+    }
+
+    /// Temp workspaces have no Cargo.lock
+    fn unlocked() -> BuildSettings {
+        BuildSettings {
+            locked: false,
+            ..Default::default()
+        }
+    }
+
+    fn write_package(dir: &Path, name: &str, extra: &str) {
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            format!(
+                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n{}",
+                name, extra
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_scan_workspace_roots_stops_at_first_manifest() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write_package(&root.join("examples/escrow"), "escrow", "[workspace]\n");
+        write_package(&root.join("examples/escrow/programs/vault"), "vault", "");
+        write_package(&root.join("examples/more/swap"), "swap", "");
+        write_package(
+            &root.join("examples/more/swap/nested"),
+            "nested",
+            "[workspace]\n",
+        );
+        write_package(&root.join("examples/.cache/stale"), "stale", "");
+        write_package(&root.join("examples/target/debug"), "debug", "");
+
+        let manifests = scan_workspace_roots(root, "examples").unwrap();
+        assert_eq!(
+            manifests,
+            vec![
+                PathBuf::from("examples/escrow/Cargo.toml"),
+                PathBuf::from("examples/more/swap/Cargo.toml"),
+            ]
+        );
+
+        let error = scan_workspace_roots(root, "missing").unwrap_err();
+        assert!(error.to_string().contains("scan directory \"missing\""));
+    }
+
+    #[test]
+    fn test_scan_workspace_roots_finds_roots_nested_in_a_workspace() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write_package(
+            root,
+            "repo",
+            "[workspace]\nmembers = [\"programs/*\"]\nexclude = [\"examples/basic\"]\n",
+        );
+        write_package(&root.join("programs/vault"), "vault", "");
+        write_package(&root.join("examples/basic"), "basic", "");
+        write_package(&root.join("examples/escrow"), "escrow", "[workspace]\n");
+        write_package(&root.join("examples/escrow/programs/swap"), "swap", "");
+        write_package(&root.join("fixtures/orphan"), "orphan", "");
+
+        // Members and packages no root claims stay out; nested roots and excluded
+        // packages are workspaces of their own
+        let manifests = scan_workspace_roots(root, ".").unwrap();
+        assert_eq!(
+            manifests,
+            vec![
+                PathBuf::from("Cargo.toml"),
+                PathBuf::from("examples/basic/Cargo.toml"),
+                PathBuf::from("examples/escrow/Cargo.toml"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_manifest_glob() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write_package(&root.join("examples/basic"), "basic", "");
+        write_package(&root.join("examples/escrow"), "escrow", "");
+        std::fs::create_dir_all(root.join("examples/empty")).unwrap();
+
+        assert_eq!(
+            expand_manifest_glob(root, "examples/*/Cargo.toml").unwrap(),
+            vec![
+                PathBuf::from("examples/basic/Cargo.toml"),
+                PathBuf::from("examples/escrow/Cargo.toml"),
+            ]
+        );
+
+        let error = expand_manifest_glob(root, "programs/*/Cargo.toml").unwrap_err();
+        assert!(error.to_string().contains("matched no Cargo.toml files"));
+    }
+
+    #[test]
+    fn test_find_workspaces_loads_each_workspace_once() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"programs/*\"]\nresolver = \"2\"\n",
+        )
+        .unwrap();
+        write_package(&root.join("programs/vault"), "vault", "");
+        write_package(&root.join("programs/escrow"), "escrow", "");
+
+        // Both members belong to the same workspace, which stands in for them
+        let found =
+            find_workspaces(root, Some("programs/*/Cargo.toml"), None, &unlocked()).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            std::fs::canonicalize(&found[0].0).unwrap(),
+            std::fs::canonicalize(root.join("Cargo.toml")).unwrap()
+        );
+        assert_eq!(found[0].1.workspace_members.len(), 2);
+    }
+
+    #[test]
+    fn test_load_candidates_skips_known_members() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"programs/*\"]\nresolver = \"2\"\n",
+        )
+        .unwrap();
+        write_package(&root.join("programs/vault"), "vault", "");
+        write_package(&root.join("programs/escrow"), "escrow", "");
+        write_package(&root.join("standalone"), "standalone", "[workspace]\n");
+
+        let candidates = vec![
+            PathBuf::from("Cargo.toml"),
+            PathBuf::from("programs/escrow/Cargo.toml"),
+            PathBuf::from("programs/vault/Cargo.toml"),
+            PathBuf::from("standalone/Cargo.toml"),
+        ];
+        let mut loaded = Vec::new();
+        let found = load_candidates(root, candidates, |manifest_path| {
+            loaded.push(relative_to(
+                &std::fs::canonicalize(root).unwrap(),
+                manifest_path.to_path_buf(),
+            ));
+            load_metadata(manifest_path, &unlocked())
+        })
+        .unwrap();
+
+        // The members come after their root, so cargo metadata never runs on them
+        assert_eq!(
+            loaded,
+            vec![
+                PathBuf::from("Cargo.toml"),
+                PathBuf::from("standalone/Cargo.toml")
+            ]
+        );
+        assert_eq!(found.len(), 2);
+    }
+
+    #[test]
+    fn test_find_workspaces_resolves_against_config_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write_package(&root.join("elves"), "elves", "");
+        write_package(&root.join("programs/vault"), "vault", "[workspace]\n");
+
+        // Relative to the elves crate, not the process working directory
+        let found = find_workspaces(
+            &root.join("elves"),
+            Some("../programs/vault/Cargo.toml"),
+            None,
+            &unlocked(),
+        )
+        .unwrap();
+        assert_eq!(found[0].0, "../programs/vault/Cargo.toml");
+        assert_eq!(found[0].1.packages[0].name.as_str(), "vault");
+    }
+
+    #[test]
+    fn test_load_metadata_lockfile_policy() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        write_package(root, "vault", "[workspace]\n");
+        let manifest_path = root.join("Cargo.toml");
+
+        // Locked by default, which needs a Cargo.lock
+        let error = load_metadata(&manifest_path, &BuildSettings::default())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("has no Cargo.lock"));
+        assert!(error.contains("`locked`"));
+
+        assert!(load_metadata(&manifest_path, &unlocked()).is_ok());
+        assert!(!root.join("Cargo.lock").exists());
+
+        let build = BuildSettings {
+            offline: true,
+            generate_lockfile: true,
+            ..Default::default()
+        };
+        assert_eq!(build.cargo_flags(), vec!["--locked", "--offline"]);
+        load_metadata(&manifest_path, &build).unwrap();
+        assert!(root.join("Cargo.lock").exists());
+    }
+
+    #[test]
+    fn test_find_workspaces_requires_one_source() {
+        let root = Path::new("/repo/elves");
+
+        let error = find_workspaces(root, None, None, &unlocked()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("needs a `manifest_path` or a `scan`"));
+
+        let error =
+            find_workspaces(root, Some("./Cargo.toml"), Some("examples"), &unlocked()).unwrap_err();
+        assert!(error.to_string().contains("use one or the other"));
+    }
 }
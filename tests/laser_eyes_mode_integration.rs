@@ -41,14 +41,20 @@ edition = "2021"
             assert_eq!(workspaces.len(), 2);
 
             // First workspace
-            assert_eq!(workspaces[0].manifest_path, "./workspace/Cargo.toml");
+            assert_eq!(
+                workspaces[0].manifest_path.as_deref(),
+                Some("./workspace/Cargo.toml")
+            );
             assert_eq!(
                 workspaces[0].only,
                 vec!["target:token_manager", "target:governance"]
             );
 
             // Second workspace
-            assert_eq!(workspaces[1].manifest_path, "./examples/Cargo.toml");
+            assert_eq!(
+                workspaces[1].manifest_path.as_deref(),
+                Some("./examples/Cargo.toml")
+            );
             assert_eq!(workspaces[1].only, vec!["target:swap*"]);
         }
         _ => panic!("Expected LaserEyes config"),
@@ -89,7 +95,7 @@ edition = "2021"
     match config {
         Config::LaserEyes { workspaces, .. } => {
             assert_eq!(workspaces.len(), 1);
-            assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+            assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
             assert_eq!(workspaces[0].only, vec!["target:my_program"]);
         }
         _ => panic!("Expected LaserEyes config"),
@@ -131,13 +137,15 @@ edition = "2021"
         Config::LaserEyes { workspaces, .. } => {
             assert_eq!(workspaces.len(), 1);
             assert_eq!(workspaces[0].only.len(), 3);
-            assert!(workspaces[0].only.contains(&"target:*_core".to_string()));
             assert!(workspaces[0]
                 .only
-                .contains(&"package:my-*-program".to_string()));
+                .contains(&"target:*_core".parse().unwrap()));
+            assert!(workspaces[0]
+                .only
+                .contains(&"package:my-*-program".parse().unwrap()));
             assert!(workspaces[0]
                 .only
-                .contains(&"path:*/core/programs/*".to_string()));
+                .contains(&"path:*/core/programs/*".parse().unwrap()));
         }
         _ => panic!("Expected LaserEyes config"),
     }
@@ -40,9 +40,10 @@ edition = "2021"
             global_deny,
             constants,
             targets,
+            ..
         } => {
             assert_eq!(workspaces.len(), 1);
-            assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+            assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
             assert_eq!(workspaces[0].deny.len(), 0);
             assert_eq!(global_deny.len(), 0);
             assert!(constants.is_empty());
@@ -89,9 +90,10 @@ edition = "2021"
             global_deny,
             constants,
             targets,
+            ..
         } => {
             assert_eq!(workspaces.len(), 1);
-            assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+            assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
             assert_eq!(workspaces[0].deny, vec!["target:test*", "package:dev*"]);
             assert_eq!(global_deny.len(), 0);
             assert!(constants.is_empty());
@@ -139,9 +141,10 @@ edition = "2021"
             global_deny,
             constants,
             targets,
+            ..
         } => {
             assert_eq!(workspaces.len(), 1);
-            assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+            assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
             assert_eq!(workspaces[0].deny, vec!["target:test*"]);
             assert_eq!(global_deny, vec!["package:apl-token"]);
             assert!(constants.is_empty());
@@ -191,6 +194,7 @@ edition = "2021"
             global_deny,
             constants,
             targets,
+            ..
         } => {
             assert_eq!(workspaces.len(), 3);
 
@@ -198,15 +202,21 @@ edition = "2021"
             assert_eq!(global_deny, vec!["package:*-test", "target:bench*"]);
 
             // First workspace with local deny patterns
-            assert_eq!(workspaces[0].manifest_path, "./Cargo.toml");
+            assert_eq!(workspaces[0].manifest_path.as_deref(), Some("./Cargo.toml"));
             assert_eq!(workspaces[0].deny, vec!["target:dev*"]);
 
             // Second workspace with no local deny patterns
-            assert_eq!(workspaces[1].manifest_path, "examples/Cargo.toml");
+            assert_eq!(
+                workspaces[1].manifest_path.as_deref(),
+                Some("examples/Cargo.toml")
+            );
             assert_eq!(workspaces[1].deny.len(), 0);
 
             // Third workspace with different local deny patterns
-            assert_eq!(workspaces[2].manifest_path, "tests/Cargo.toml");
+            assert_eq!(
+                workspaces[2].manifest_path.as_deref(),
+                Some("tests/Cargo.toml")
+            );
             assert_eq!(workspaces[2].deny, vec!["path:*/integration/*"]);
 
             assert!(constants.is_empty());